@@ -6,6 +6,10 @@ use std::io::Read;
 
 use crate::archiver;
 use crate::configuration;
+use crate::manifest;
+use crate::naming;
+use crate::retention;
+use crate::signature;
 use crate::util;
 
 /// regex string matching
@@ -29,68 +33,43 @@ fn matches(pattern: &str, text: &str) -> bool {
 	return true;
 }
 
-/// 0 padding
-fn zero_pad_2(n: u32) -> String {
-	return format!("{:0>2}", n);
-}
-
 /// Extract reserved keywords.
-fn extract_keywords(str: &str, name: &str) -> String {
-	use chrono::Datelike;
-	use chrono::Timelike;
-
-	let now = std::time::SystemTime::now();
-	let now = chrono::DateTime::<chrono::Local>::from(now);
-
-	let mut result = str.to_string();
-
-	// for Linux
-	if result.contains("%Y") {
-		result = result.replace("%Y", &now.year().to_string());
-	}
-	if result.contains("%m") {
-		result = result.replace("%m", &zero_pad_2(now.month()));
-	}
-	if result.contains("%d") {
-		result = result.replace("%d", &zero_pad_2(now.day()));
-	}
-	if result.contains("%H") {
-		result = result.replace("%H", &zero_pad_2(now.hour()));
-	}
-	if result.contains("%M") {
-		result = result.replace("%M", &zero_pad_2(now.minute()));
-	}
-	if result.contains("%S") {
-		result = result.replace("%S", &zero_pad_2(now.second()));
-	}
-	if result.contains("%0") {
-		result = result.replace("%0", name);
-	}
-
-	// for Windows
-	if result.contains("{Y}") {
-		result = result.replace("{Y}", &now.year().to_string());
-	}
-	if result.contains("{m}") {
-		result = result.replace("{m}", &zero_pad_2(now.month()));
-	}
-	if result.contains("{d}") {
-		result = result.replace("{d}", &zero_pad_2(now.day()));
-	}
-	if result.contains("{H}") {
-		result = result.replace("{H}", &zero_pad_2(now.hour()));
-	}
-	if result.contains("{M}") {
-		result = result.replace("{M}", &zero_pad_2(now.minute()));
-	}
-	if result.contains("{S}") {
-		result = result.replace("{S}", &zero_pad_2(now.second()));
-	}
-	if result.contains("{0}") {
-		result = result.replace("{0}", name);
+///
+/// # Arguments
+/// * `template` Template of the path to archive. See [naming].
+/// * `context` Values referred by the template. `sequence` is updated.
+fn extract_keywords(template: &naming::NameTemplate, context: &mut naming::NamingContext) -> Result<String, Box<dyn std::error::Error>> {
+	if !template.has_sequence() {
+		return template.render(context);
+	}
+
+	// Find the first sequence number which does not collide with existing files.
+	loop {
+		let result = template.render(context)?;
+		if !std::path::Path::new(&result).exists() {
+			return Ok(result);
+		}
+		if context.sequence == u32::MAX {
+			return Err("No available sequence number.".into());
+		}
+		context.sequence += 1;
 	}
+}
 
-	return result;
+/// Write a new archive.
+///
+/// # Arguments
+/// * `settings` [configuration::Settings].
+/// * `path_to_archive` Path to a new archive.
+/// * `paths` Canonical paths to directories or files.
+/// * `create_root` Create a root directory.
+fn write_archive(settings: &configuration::Settings, path_to_archive: &str, paths: &[std::path::PathBuf], create_root: bool) -> Result<(), Box<dyn std::error::Error>> {
+	let mut archiver = archiver::ZipArchiver::new(path_to_archive, settings)?;
+	for path in paths {
+		archiver.append("", path, settings, create_root)?;
+	}
+	archiver.finish()?;
+	return Ok(());
 }
 
 /// Read file to the end.
@@ -105,15 +84,21 @@ fn read_file_to_end(path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
 ///
 /// Application core
 ///
-pub struct Zipper;
+pub struct Zipper {
+	/// Show what would be done without writing anything.
+	dry_run: bool,
+}
 
 impl Zipper {
 	/// Returns a new instance of [Zipper].
 	///
+	/// # Arguments
+	/// * `dry_run` Show what would be done without writing anything.
+	///
 	/// # Returns
 	/// A new instance of [Zipper].
-	pub fn new() -> Zipper {
-		let instance = Zipper {};
+	pub fn new(dry_run: bool) -> Zipper {
+		let instance = Zipper { dry_run };
 		return instance;
 	}
 
@@ -122,27 +107,130 @@ impl Zipper {
 	/// # Arguments
 	/// * `settings` [configuration::Settings].
 	/// * `path_to_archive` Path to a new archive.
-	/// * `path` Path to a directory.
+	/// * `sources` Paths to directories or files. One from the command line, or the sources of a job. `{0}` is the name of the first one.
 	/// * `create_root` Create a root directory.
-	pub fn archive(&self, settings: &configuration::Settings, path_to_archive: &str, path: &str, create_root: bool) -> Result<(), Box<dyn std::error::Error>> {
-		// Canonicalize path.
-		let path = util::canonicalize_path(path)?;
-
-		let name = std::path::Path::new(&path).file_name();
-		let name = name.unwrap().to_str().unwrap();
+	pub fn archive(
+		&self,
+		settings: &configuration::Settings,
+		path_to_archive: &str,
+		sources: &[std::path::PathBuf],
+		create_root: bool,
+	) -> Result<(), Box<dyn std::error::Error>> {
+		if sources.is_empty() {
+			return Err("No sources to archive.".into());
+		}
+
+		// Canonicalize paths.
+		let mut paths: Vec<std::path::PathBuf> = Vec::new();
+		for source in sources {
+			let result = util::canonicalize(source);
+			if result.is_err() {
+				return Err(format!("Invalid source [{}]. reason: {}", source.display(), result.err().unwrap()).into());
+			}
+			paths.push(result.unwrap());
+		}
+		let shown_paths = paths.iter().map(|e| e.display().to_string()).collect::<Vec<String>>().join(", ");
+
+		// The root directory has no name.
+		let name = match paths[0].file_name() {
+			None => "root".to_string(),
+			Some(name) => name.to_string_lossy().to_string(),
+		};
+
+		// Validate settings before archiving.
+		settings.compression_method()?;
+		settings.entry_filter()?;
+		settings.name_policy()?;
+		settings.name_encoding()?;
+		settings.name_normalization()?;
+		let retention = retention::RetentionPolicy::from_settings(settings)?;
 
 		// Extract special keywords.
-		let path_to_archive = extract_keywords(path_to_archive, name);
+		let template = naming::NameTemplate::parse(path_to_archive)?;
+		let mut context = naming::NamingContext::new(&name, &paths[0]);
+		let path_to_archive = extract_keywords(&template, &mut context)?;
+		if retention.is_some() {
+			template.to_regex(&context)?;
+		}
+
+		if self.dry_run {
+			println!("[INFO] (dry-run) archiving ... {} >> {}", shown_paths, &path_to_archive);
+			let mut archiver = archiver::ZipArchiver::dry_run(settings)?;
+			for description in archiver.describe_filters() {
+				println!("[INFO] (dry-run) filter: {}", description);
+			}
+			for path in &paths {
+				archiver.append("", path, settings, create_root)?;
+			}
+			archiver.finish()?;
+		} else {
+			println!("[INFO] archiving ... {} >> {}", shown_paths, &path_to_archive);
+
+			// Create a new archive. An incomplete archive is removed on error.
+			let result = write_archive(settings, &path_to_archive, &paths, create_root);
+			if result.is_err() {
+				if std::fs::remove_file(&path_to_archive).is_ok() {
+					println!("[INFO] REMOVE {} (incomplete)", &path_to_archive);
+				}
+				return result;
+			}
+
+			// Checksum of the archive.
+			if settings.checksum_file == Some(true) {
+				let path = manifest::write_checksum_file(&path_to_archive)?;
+				println!("[INFO] checksum written. [{}]", path);
+			}
+
+			// Signature of the archive.
+			if let Some(secret_key_path) = &settings.sign {
+				let path = signature::sign(&path_to_archive, secret_key_path)?;
+				println!("[INFO] signature written. [{}]", path);
+			}
+		}
+
+		// Remove expired archives.
+		if let Some(retention) = retention {
+			retention.apply(&template, &context, &path_to_archive, self.dry_run)?;
+		}
 
-		println!("[INFO] archiving ... {} >> {}", &path, &path_to_archive);
-
-		// Remove existing .zip file.
-		util::unlink(&path_to_archive)?;
-
-		// Create a new archive.
-		let mut archiver = archiver::ZipArchiver::new(&path_to_archive)?;
-		archiver.append("", &path, &settings, create_root)?;
+		return Ok(());
+	}
 
+	/// Run named jobs in settings.toml.
+	///
+	/// # Arguments
+	/// * `settings` [configuration::Settings].
+	/// * `names` Names of the jobs.
+	pub fn run_jobs(&self, settings: &configuration::Settings, names: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+		if names.is_empty() {
+			return Err("No jobs to run.".into());
+		}
+
+		let mut failed: Vec<String> = Vec::new();
+		for name in names {
+			let stopwatch = util::Stopwatch::new();
+			println!("[INFO] job [{}] started.", name);
+			let result = self.run_job(settings, name);
+			if result.is_err() {
+				eprintln!("[ERROR] job [{}] failed. reason: {}", name, result.err().unwrap());
+				failed.push(name.to_string());
+				continue;
+			}
+			println!("[INFO] job [{}] Ok. ({})", name, stopwatch);
+		}
+
+		println!("[INFO] jobs: {} succeeded, {} failed.", names.len() - failed.len(), failed.len());
+		if !failed.is_empty() {
+			return Err(format!("Failed jobs: {}", failed.join(", ")).into());
+		}
 		return Ok(());
 	}
+
+	/// Run a named job.
+	fn run_job(&self, settings: &configuration::Settings, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+		let (job, settings) = settings.job(name)?;
+		let create_root = job.create_root.unwrap_or(true);
+		let sources = job.sources.iter().map(std::path::PathBuf::from).collect::<Vec<std::path::PathBuf>>();
+		return self.archive(&settings, &job.destination, &sources, create_root);
+	}
 }
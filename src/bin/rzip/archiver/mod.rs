@@ -3,94 +3,892 @@
 //!
 
 use crate::configuration;
+use crate::extra_field;
+use crate::incremental;
+use crate::manifest;
+use crate::names;
+use crate::portability;
 use crate::util;
 
 /// Zip archiver class
 pub struct ZipArchiver {
-	archiver: zip::ZipWriter<std::fs::File>,
+	/// Writer. `None` in dry-run.
+	archiver: Option<zip::ZipWriter<std::fs::File>>,
+
+	/// Path to the archive. `None` in dry-run.
+	path: Option<String>,
+
+	/// `.rzipignore` of the ancestors being walked. (internal path of its directory, patterns)
+	ignores: Vec<(String, configuration::IgnoreFile)>,
+
+	/// Filters on metadata.
+	filter: configuration::EntryFilter,
+
+	/// Depth of the directory being walked. (0 = source)
+	depth: usize,
+
+	/// Incremental archiving. `None` archives all files.
+	incremental: Option<incremental::Incremental>,
+
+	/// Manifest written into the archive.
+	manifest: Option<manifest::Manifest>,
+
+	/// Normalize permissions and store timestamps in UTC.
+	reproducible: bool,
+
+	/// Order of the entries in a directory. `None` keeps the order of the file system.
+	sort_order: Option<configuration::SortOrder>,
+
+	/// Timestamps are clamped to `SOURCE_DATE_EPOCH`.
+	source_date_epoch: Option<std::time::SystemTime>,
+
+	/// Handling of timestamps out of the DOS date range.
+	time_policy: configuration::TimePolicy,
+
+	/// Handling of file names which are not valid UTF-8.
+	name_policy: configuration::NamePolicy,
+
+	/// Encoding of the names in the archive.
+	name_encoding: configuration::NameEncoding,
+
+	/// Unicode normalization of the names.
+	name_normalization: configuration::NameNormalization,
+
+	/// Names of the entries in NFC, and the names and the paths of their sources. To detect collisions.
+	normalized_names: std::collections::HashMap<String, (String, std::path::PathBuf)>,
+
+	/// Number of the entries which collided after normalization.
+	collisions: usize,
+
+	/// Check of names which break on Windows or macOS.
+	portability: portability::PortabilityLint,
+
+	/// Store extended attributes of files.
+	xattrs: bool,
+
+	/// Store hard links once. Later links refer to the first entry.
+	store_hard_links: bool,
+
+	/// Files with hard links stored already. ((device, inode), (name of the entry, SHA-256))
+	hard_links: std::collections::HashMap<(u64, u64), (String, String)>,
+
+	/// Number and size of the files found as hard links to the stored ones.
+	linked_files: (usize, u64),
+
+	/// Do not descend into directories on other file systems.
+	one_file_system: bool,
+
+	/// Device of the source being walked.
+	root_device: Option<u64>,
+
+	/// Names rewritten after the archive is finished. (name written by the zip writer, bytes to store)
+	renames: std::collections::HashMap<String, names::RawName>,
+
+	/// Entries stored with raw bytes in their names or their ancestors. (name written by the zip writer without "/", bytes)
+	raw_paths: std::collections::HashMap<String, Vec<u8>>,
+
+	/// Directories written with extra fields, and their permissions. Their attributes are fixed after the archive is finished.
+	directories: std::collections::HashMap<String, u32>,
 }
 
 impl ZipArchiver {
-	/// Returns a new instance of [ZipArchiver].
-	pub fn new(path_to_archive: &str) -> Result<ZipArchiver, Box<dyn std::error::Error>> {
+	/// Returns a new instance of [ZipArchiver]. Existing file is replaced.
+	///
+	/// # Arguments
+	/// * `path_to_archive` Path to a new archive.
+	/// * `settings` [configuration::Settings].
+	pub fn new(path_to_archive: &str, settings: &configuration::Settings) -> Result<ZipArchiver, Box<dyn std::error::Error>> {
+		// The previous state may be read from the existing archive.
+		let mut instance = ZipArchiver::dry_run(settings)?;
+
+		// Remove existing .zip file.
+		util::unlink(path_to_archive)?;
+
 		let file = std::fs::File::create(path_to_archive)?;
-		let archiver = zip::ZipWriter::new(file);
-		let instance = ZipArchiver { archiver };
+		instance.archiver = Some(zip::ZipWriter::new(file));
+		instance.path = Some(path_to_archive.to_string());
+		return Ok(instance);
+	}
+
+	/// Returns a new instance of [ZipArchiver] which writes nothing.
+	///
+	/// # Arguments
+	/// * `settings` [configuration::Settings].
+	pub fn dry_run(settings: &configuration::Settings) -> Result<ZipArchiver, Box<dyn std::error::Error>> {
+		let instance = ZipArchiver {
+			archiver: None,
+			path: None,
+			ignores: Vec::new(),
+			filter: settings.entry_filter()?,
+			depth: 0,
+			incremental: incremental::Incremental::from_settings(settings)?,
+			manifest: manifest::Manifest::from_settings(settings)?,
+			reproducible: settings.reproducible == Some(true),
+			sort_order: settings.sort_order()?,
+			source_date_epoch: util::source_date_epoch()?,
+			time_policy: settings.time_policy()?,
+			name_policy: settings.name_policy()?,
+			name_encoding: settings.name_encoding()?,
+			name_normalization: settings.name_normalization()?,
+			normalized_names: std::collections::HashMap::new(),
+			collisions: 0,
+			portability: portability::PortabilityLint::from_settings(settings),
+			xattrs: settings.xattrs == Some(true),
+			store_hard_links: settings.hard_links == Some(true),
+			hard_links: std::collections::HashMap::new(),
+			linked_files: (0, 0),
+			one_file_system: settings.one_file_system == Some(true),
+			root_device: None,
+			renames: std::collections::HashMap::new(),
+			raw_paths: std::collections::HashMap::new(),
+			directories: std::collections::HashMap::new(),
+		};
 		return Ok(instance);
 	}
 
+	/// Descriptions of the active filters.
+	pub fn describe_filters(&self) -> &Vec<String> {
+		return self.filter.describe();
+	}
+
+	/// Last modified time of the entry. Clamped to `SOURCE_DATE_EPOCH`.
+	fn entry_time(&self, meta: &std::fs::Metadata) -> Result<std::time::SystemTime, Box<dyn std::error::Error>> {
+		let time = meta.modified()?;
+		if let Some(epoch) = self.source_date_epoch {
+			if epoch < time {
+				return Ok(epoch);
+			}
+		}
+		return Ok(time);
+	}
+
+	/// Unix permissions of the entry. Normalized to `0755` or `0644` in reproducible mode.
+	fn entry_mode(&self, meta: &std::fs::Metadata) -> Option<u32> {
+		let mode = get_unix_permissions_as_u8(meta);
+		if !self.reproducible {
+			return mode;
+		}
+		if meta.is_dir() || mode.unwrap_or(0) & 0o111 != 0 {
+			return Some(0o755);
+		}
+		return Some(0o644);
+	}
+
+	/// Convert to DOS date time. UTC in reproducible mode, otherwise local time.
+	///
+	/// # Arguments
+	/// * `time` Timestamp.
+	/// * `name` Name of the entry. For warnings.
+	fn to_ziptime(&self, time: std::time::SystemTime, name: &str) -> Result<zip::DateTime, Box<dyn std::error::Error>> {
+		use crate::util::SystemTimeHelper;
+
+		let converter = if self.reproducible {
+			std::time::SystemTime::as_utc_ziptime
+		} else {
+			std::time::SystemTime::as_ziptime
+		};
+		if let Some(converted) = converter(&time) {
+			return Ok(converted);
+		}
+
+		// Out of the DOS date range. The true value is kept in the extended timestamp if it fits in 32 bits.
+		let shown = chrono::DateTime::<chrono::Utc>::from(time).format("%Y-%m-%d %H:%M:%S UTC");
+		let substitute = match self.time_policy {
+			configuration::TimePolicy::Error => {
+				return Err(format!("Timestamp of [{}] is out of the DOS date range. ({})", name, shown).into());
+			}
+			configuration::TimePolicy::Clamp => {
+				// The bound is chosen in the same time zone as the conversion.
+				use chrono::Datelike;
+				let year = if self.reproducible {
+					chrono::DateTime::<chrono::Utc>::from(time).year()
+				} else {
+					chrono::DateTime::<chrono::Local>::from(time).year()
+				};
+				if year < 1980 {
+					zip::DateTime::default()
+				} else {
+					zip::DateTime::from_date_and_time(2107, 12, 31, 23, 59, 58).unwrap()
+				}
+			}
+			configuration::TimePolicy::Now => converter(&std::time::SystemTime::now()).unwrap_or_default(),
+		};
+		println!(
+			"[WARN] Timestamp of {} is out of the DOS date range. ({}) {:04}-{:02}-{:02} {:02}:{:02}:{:02} is stored instead.",
+			name,
+			shown,
+			substitute.year(),
+			substitute.month(),
+			substitute.day(),
+			substitute.hour(),
+			substitute.minute(),
+			substitute.second()
+		);
+		return Ok(substitute);
+	}
+
+	/// Map metadata of an entry to [zip::write::FileOptions]. Used for both directories and files.
+	///
+	/// # Arguments
+	/// * `name` Name of the entry.
+	/// * `meta` Metadata of the entry.
+	/// * `method` Compression method. `Stored` for directories.
+	/// * `level` Compression level. Ignored for `Stored`.
+	fn create_attributes(
+		&self,
+		name: &str,
+		meta: &std::fs::Metadata,
+		method: zip::CompressionMethod,
+		level: Option<i32>,
+	) -> Result<zip::write::FileOptions, Box<dyn std::error::Error>> {
+		let options = zip::write::FileOptions::default();
+
+		// compression method
+		let options = if method == zip::CompressionMethod::Stored {
+			// zip rejects any level for stored entries. A global level is meant for the other methods.
+			options.compression_method(method)
+		} else {
+			options.compression_method(method).compression_level(level)
+		};
+
+		// last modified time
+		let options = options.last_modified_time(self.to_ziptime(self.entry_time(meta)?, name)?);
+
+		// permissions (including executable bits)
+		let options = match self.entry_mode(meta) {
+			None => options,
+			Some(mode) => options.unix_permissions(mode),
+		};
+
+		return Ok(options);
+	}
+
+	/// Create extra fields of an entry. (extended timestamp, Unix owner and extended attributes)
+	///
+	/// # Arguments
+	/// * `path` Path to the file or the directory.
+	/// * `name` Name of the entry. For warnings.
+	/// * `meta` Metadata of the entry.
+	/// * `raw` Name rewritten later. Room is left for its Unicode path extra field.
+	///
+	/// # Returns
+	/// Extra fields of the local header and the central header.
+	fn create_extra_fields(
+		&self,
+		path: &std::path::Path,
+		name: &str,
+		meta: &std::fs::Metadata,
+		raw: Option<&names::RawName>,
+	) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+		let mut timestamp = extra_field::ExtendedTimestamp {
+			mtime: extra_field::to_unix_time(self.entry_time(meta)?),
+			atime: None,
+			ctime: None,
+		};
+
+		// Access time, change time and owner are specific to the host.
+		if !self.reproducible {
+			timestamp.atime = meta.accessed().ok().and_then(extra_field::to_unix_time);
+			#[cfg(unix)]
+			{
+				use std::convert::TryFrom;
+				use std::os::unix::fs::MetadataExt;
+				timestamp.ctime = i32::try_from(meta.ctime()).ok();
+			}
+		}
+
+		let mut local = timestamp.to_local();
+		let mut central = timestamp.to_central();
+
+		#[cfg(unix)]
+		if !self.reproducible {
+			use std::os::unix::fs::MetadataExt;
+			let owner = extra_field::unix_owner(meta.uid(), meta.gid());
+			local.extend_from_slice(&owner);
+			central.extend_from_slice(&owner);
+		}
+
+		// Extended attributes are stored in the local header only.
+		#[cfg(unix)]
+		if self.xattrs {
+			let reserved = raw.map(|e| e.extra_size()).unwrap_or(0);
+			let xattrs = read_xattrs(path, &self.display(name), extra_field::MAX_EXTRA_SIZE.saturating_sub(local.len() + reserved));
+			local.extend_from_slice(&xattrs);
+		}
+		#[cfg(not(unix))]
+		let _ = (path, name, raw);
+
+		return Ok((local, central));
+	}
+
+	/// Attributes of the entries generated by rzip. (manifest, incremental state)
+	fn generated_file_options(&self) -> Result<zip::write::FileOptions, Box<dyn std::error::Error>> {
+		let options = zip::write::FileOptions::default();
+		if let Some(epoch) = self.source_date_epoch {
+			return Ok(options.last_modified_time(self.to_ziptime(epoch, "SOURCE_DATE_EPOCH")?));
+		}
+		if self.reproducible {
+			return Ok(options.last_modified_time(zip::DateTime::default()));
+		}
+		return Ok(options);
+	}
+
+	/// Finish the archive. The manifest and the incremental state are written into the archive.
+	pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+		use std::io::Write;
+
+		if 0 < self.collisions {
+			println!("[WARN] {} entries collided after Unicode normalization.", self.collisions);
+		}
+		let (count, size) = self.linked_files;
+		if 0 < count && self.store_hard_links {
+			println!("[INFO] {} hard links are stored as links. ({} bytes saved)", count, size);
+		} else if 0 < count {
+			println!(
+				"[INFO] {} hard links are stored in full. ({} bytes wasted, --hard-links stores them once)",
+				count, size
+			);
+		}
+		if 0 < self.portability.problems() {
+			println!("[WARN] {} names are not portable to Windows or macOS.", self.portability.problems());
+		}
+
+		let options = self.generated_file_options()?;
+
+		if let Some(manifest) = &self.manifest {
+			if let Some(archiver) = self.archiver.as_mut() {
+				println!("  adding: {} (deflated)", manifest.entry_name());
+				archiver.start_file(manifest.entry_name(), options)?;
+				archiver.write_all(manifest.render()?.as_bytes())?;
+			}
+		}
+
+		if let Some(incremental) = &self.incremental {
+			let deletions = incremental.deletions();
+			for path in &deletions {
+				println!("[INFO] DELETED {}", path);
+			}
+
+			if let Some(archiver) = self.archiver.as_mut() {
+				if incremental.records_deletions() {
+					println!("  adding: {} (deflated)", incremental::DELETIONS_ENTRY_NAME);
+					archiver.start_file(incremental::DELETIONS_ENTRY_NAME, options)?;
+					for path in &deletions {
+						writeln!(archiver, "{}", path)?;
+					}
+				}
+				println!("  adding: {} (deflated)", incremental::STATE_ENTRY_NAME);
+				archiver.start_file(incremental::STATE_ENTRY_NAME, options)?;
+				archiver.write_all(incremental.current().to_json()?.as_bytes())?;
+			}
+		}
+
+		let archiver = self.archiver.take();
+		if archiver.is_none() {
+			return Ok(());
+		}
+		archiver.unwrap().finish()?;
+
+		// Directories are marked as such. (The zip writer marks every entry with extra fields as a file.)
+		if !self.directories.is_empty() {
+			mark_directories(self.path.as_deref().unwrap(), &self.directories)?;
+		}
+
+		// Names which are not valid UTF-8, or in the legacy encoding.
+		if !self.renames.is_empty() {
+			names::rewrite_names(self.path.as_deref().unwrap(), &self.renames)?;
+		}
+
+		if let Some(incremental) = &self.incremental {
+			incremental.save()?;
+		}
+
+		return Ok(());
+	}
+
+	/// Name of the entry in the archive. Names which are not valid UTF-8 are handled by the policy.
+	///
+	/// # Arguments
+	/// * `path` Path to the entry.
+	///
+	/// # Returns
+	/// `None` if the entry is skipped.
+	fn entry_name(&self, path: &std::path::Path) -> Option<String> {
+		let name = path.file_name().unwrap_or(path.as_os_str());
+		if let Some(name) = name.to_str() {
+			return Some(name.to_string());
+		}
+
+		let escaped = names::escape(name);
+		let name = match self.name_policy {
+			configuration::NamePolicy::Skip => {
+				println!("[WARN] SKIP {} (not UTF-8)", escaped);
+				return None;
+			}
+			configuration::NamePolicy::Escape => escaped.clone(),
+			configuration::NamePolicy::Raw => names::to_placeholder(name).unwrap_or_else(|| escaped.clone()),
+		};
+		println!("[WARN] Name of {} is not UTF-8.", escaped);
+		return Some(name);
+	}
+
+	/// Find an entry whose name is the same after normalization.
+	///
+	/// # Arguments
+	/// * `internal_path` Name of the entry.
+	/// * `path` Path to the source.
+	///
+	/// # Returns
+	/// `true` if the entry is already stored with the same name.
+	fn is_duplicate(&mut self, internal_path: &str, path: &std::path::Path) -> bool {
+		use unicode_normalization::UnicodeNormalization;
+
+		let key = internal_path.nfc().collect::<String>();
+		let (stored, other) = match self.normalized_names.get(&key) {
+			None => {
+				self.normalized_names.insert(key, (internal_path.to_string(), path.to_path_buf()));
+				return false;
+			}
+			Some(other) => other,
+		};
+
+		// Exactly the same name. An escaped name of a non-UTF-8 name may be the same as a real name.
+		if stored == internal_path {
+			let escaped = [path, other.as_path()]
+				.iter()
+				.any(|e| e.file_name().map(|name| name.to_str().is_none()).unwrap_or(false));
+			let reason = if escaped { "escaped name of a non-UTF-8 name" } else { "the same name" };
+			println!(
+				"[WARN] COLLISION {} is stored as {} already. ({}, {})",
+				path.display(),
+				self.display(internal_path),
+				other.display(),
+				reason
+			);
+			return true;
+		}
+
+		// Names which differ only in normalization.
+		self.collisions += 1;
+		if self.name_normalization == configuration::NameNormalization::None {
+			println!("[WARN] COLLISION {} and {} differ only in Unicode normalization.", other.display(), path.display());
+			return false;
+		}
+		println!(
+			"[WARN] COLLISION {} is stored as {} already. ({})",
+			path.display(),
+			self.display(internal_path),
+			other.display()
+		);
+		return true;
+	}
+
+	/// Record the bytes of the entry if its name or one of its ancestors is stored raw.
+	/// They are not inferred from the placeholders, since real names may contain the same characters.
+	///
+	/// # Arguments
+	/// * `base_name` Name of the parent written by the zip writer.
+	/// * `internal_path` Name of the entry written by the zip writer.
+	/// * `path` Path to the entry.
+	fn record_raw_path(&mut self, base_name: &str, internal_path: &str, path: &std::path::Path) {
+		let name = path.file_name().unwrap_or(path.as_os_str());
+		let own = match self.name_policy {
+			configuration::NamePolicy::Raw if name.to_str().is_none() => names::name_bytes(name),
+			_ => None,
+		};
+		let parent = self.raw_paths.get(base_name).cloned();
+		if own.is_none() && parent.is_none() {
+			return;
+		}
+
+		let mut bytes = parent.unwrap_or_else(|| base_name.as_bytes().to_vec());
+		if !base_name.is_empty() {
+			bytes.push(b'/');
+		}
+		match own {
+			None => bytes.extend_from_slice(internal_path.rsplit('/').next().unwrap().as_bytes()),
+			Some(own) => bytes.extend_from_slice(&own),
+		}
+		self.raw_paths.insert(internal_path.to_string(), bytes);
+	}
+
+	/// Bytes of the entry stored raw. See [ZipArchiver::record_raw_path].
+	///
+	/// # Arguments
+	/// * `internal_path` Name of the entry written by the zip writer. Directories end with "/".
+	///
+	/// # Returns
+	/// `None` if the name is valid UTF-8.
+	fn raw_bytes(&self, internal_path: &str) -> Option<Vec<u8>> {
+		let key = internal_path.strip_suffix('/').unwrap_or(internal_path);
+		let mut bytes = self.raw_paths.get(key)?.clone();
+		if key.len() < internal_path.len() {
+			bytes.push(b'/');
+		}
+		return Some(bytes);
+	}
+
+	/// Printable form of the entry name. Raw bytes are shown as `%XX`.
+	///
+	/// # Arguments
+	/// * `internal_path` Name of the entry written by the zip writer.
+	fn display(&self, internal_path: &str) -> String {
+		return match self.raw_bytes(internal_path) {
+			None => internal_path.to_string(),
+			Some(bytes) => names::escape_bytes(&bytes),
+		};
+	}
+
+	/// Bytes of the name to store instead of the placeholder, or the name in the legacy encoding.
+	///
+	/// # Arguments
+	/// * `internal_path` Name of the entry written by the zip writer.
+	///
+	/// # Returns
+	/// `None` if the name is stored as written.
+	fn raw_name(&self, internal_path: &str) -> Option<names::RawName> {
+		if self.archiver.is_none() || internal_path.is_ascii() {
+			return None;
+		}
+
+		if let Some(bytes) = self.raw_bytes(internal_path) {
+			return Some(names::RawName {
+				bytes,
+				unicode: None,
+				utf8: false,
+			});
+		}
+		if self.name_encoding == configuration::NameEncoding::Utf8 {
+			return None;
+		}
+		return match names::encode(internal_path, self.name_encoding) {
+			None => {
+				println!(
+					"[WARN] Name of {} cannot be encoded in {:?}. It is stored in UTF-8.",
+					internal_path, self.name_encoding
+				);
+				None
+			}
+			Some(bytes) => Some(names::RawName {
+				bytes,
+				unicode: Some(internal_path.to_string()),
+				utf8: false,
+			}),
+		};
+	}
+
+	/// Rewrite the name after the archive is finished.
+	///
+	/// # Arguments
+	/// * `internal_path` Name of the entry written by the zip writer.
+	/// * `raw` Name to store. See [ZipArchiver::raw_name].
+	fn rename_if_needed(&mut self, internal_path: &str, raw: Option<names::RawName>) -> Result<(), Box<dyn std::error::Error>> {
+		if let Some(raw) = raw {
+			self.renames.insert(internal_path.to_string(), raw);
+		}
+		return self.check_rewrite_limits();
+	}
+
+	/// Names are rewritten after the archive is finished, which does not work over the limits of ZIP without ZIP64.
+	/// Fails as soon as the archive reaches them, not after writing everything.
+	fn check_rewrite_limits(&self) -> Result<(), Box<dyn std::error::Error>> {
+		let path = match &self.path {
+			Some(path) if !self.renames.is_empty() => path,
+			_ => return Ok(()),
+		};
+		let count = self.normalized_names.len();
+		let size = std::fs::metadata(path)?.len();
+		if count < 0xFFFF && size < 0xFFFF_FFFF {
+			return Ok(());
+		}
+		let message = format!(
+			"Names in {:?} or raw names cannot be stored in archives over 4 GiB or 65535 entries. ({} entries, {} bytes so far)",
+			self.name_encoding, count, size
+		);
+		return Err(message.into());
+	}
+
+	/// Create an entry of a hard link to a file stored already. It has no content.
+	///
+	/// # Arguments
+	/// * `internal_path` Name of the entry.
+	/// * `target` Name and SHA-256 of the stored file.
+	/// * `meta` Metadata of the file.
+	fn append_hard_link(&mut self, internal_path: &str, target: &(String, String), meta: &std::fs::Metadata) -> Result<(), Box<dyn std::error::Error>> {
+		let (target, sha256) = target;
+		let options = self.create_attributes(internal_path, meta, zip::CompressionMethod::Stored, None)?;
+
+		println!("  adding: {} (hard link to {})", self.display(internal_path), self.display(target));
+
+		if let Some(archiver) = self.archiver.as_mut() {
+			use std::io::Write;
+
+			// Name of the target as stored in its header.
+			let target = match self.renames.get(target) {
+				None => target.as_bytes().to_vec(),
+				Some(raw) => raw.bytes.clone(),
+			};
+			let link = extra_field::hard_link(&target);
+			archiver.start_file_with_extra_data(internal_path, options)?;
+			archiver.write_all(&link)?;
+			archiver.end_local_start_central_extra_data()?;
+			archiver.write_all(&link)?;
+			archiver.end_extra_data()?;
+			let raw = self.raw_name(internal_path);
+			self.rename_if_needed(internal_path, raw)?;
+		}
+
+		let shown_path = self.display(internal_path);
+		if let Some(incremental) = self.incremental.as_mut() {
+			incremental.record(internal_path, &shown_path, incremental::FileState::new(meta, sha256)?);
+		}
+		let (mtime, mode) = (self.entry_time(meta)?, self.entry_mode(meta));
+		if let Some(manifest) = self.manifest.as_mut() {
+			manifest.record(manifest::ManifestEntry::new(&shown_path, meta, mtime, mode, Some(sha256.clone())));
+		}
+		return Ok(());
+	}
+
+	/// Record an entry excluded in this run, so that it is not reported as deleted.
+	fn skip(&mut self, internal_path: &str, is_dir: bool) {
+		if let Some(incremental) = self.incremental.as_mut() {
+			incremental.skip(internal_path, is_dir);
+		}
+	}
+
+	/// Returns `true` if the entry is excluded by `.rzipignore` of the ancestors.
+	///
+	/// # Arguments
+	/// * `internal_path` Relative path from the root. "path/to/name"
+	/// * `name` Name of the entry.
+	/// * `is_dir` The entry is a directory.
+	fn is_ignored(&self, internal_path: &str, name: &str, is_dir: bool) -> bool {
+		for (base, ignore) in &self.ignores {
+			let relative = if base == "" {
+				internal_path
+			} else {
+				internal_path.strip_prefix(base.as_str()).unwrap_or(internal_path).trim_start_matches('/')
+			};
+			if ignore.is_excluded(relative, name, is_dir) {
+				return true;
+			}
+		}
+		return false;
+	}
+
 	/// Create a new entry into archive.
 	///
 	/// # Arguments
 	/// * `base_name` Relative path of folder.
 	/// * `path` Path to a new entry.
 	/// * `create_root` Whether it creates root folder or not.
-	pub fn append(&mut self, base_name: &str, path: &str, settings: &configuration::Settings, create_root: bool) -> Result<(), Box<dyn std::error::Error>> {
-		use crate::util::DirEntityHelper;
-		use crate::util::PathHelper;
+	pub fn append(&mut self, base_name: &str, path: &std::path::Path, settings: &configuration::Settings, create_root: bool) -> Result<(), Box<dyn std::error::Error>> {
+		use sha2::Digest;
 		use std::io::Read;
 		use std::io::Write;
 
-		let unknown = std::path::Path::new(path);
+		self.check_rewrite_limits()?;
+
+		// The root directory has no name. Its contents are stored at the top.
+		let create_root = create_root && path.file_name().is_some();
+
+		let unknown = path;
 		if unknown.is_dir() {
 			// name of directory
-			let name = unknown.name_as_str();
+			let name = match self.entry_name(unknown) {
+				None => return Ok(()),
+				Some(name) => name,
+			};
+			let name = name.as_str();
+			let shown = names::escape(unknown.file_name().unwrap_or(unknown.as_os_str()));
+			// Relative path from the root. "path/to/name"
+			let candidate = util::build_archive_internal_path(base_name, name, self.name_normalization);
+			let internal_path = if create_root { candidate.clone() } else { String::new() };
+
 			// validate its name
-			if !settings.is_valid_dir(name) {
-				println!("[INFO] IGNORE {}", name);
+			if !settings.is_valid_dir(name) || self.is_ignored(&candidate, name, true) {
+				println!("[INFO] IGNORE {}", shown);
+				self.skip(&internal_path, true);
 				return Ok(());
 			}
 
-			// Relative path from the root. "path/to/name"
-			let internal_path = if create_root {
-				util::build_archive_internal_path(base_name, name)
-			} else {
-				String::new()
-			};
+			// Directories on other file systems. (mounted shares, bind mounts, ...)
+			if self.one_file_system {
+				let device = get_device(&unknown.metadata()?);
+				if self.depth == 0 {
+					self.root_device = device;
+				} else if device != self.root_device {
+					println!("[INFO] IGNORE {} (on another file system)", path.display());
+					self.skip(&internal_path, true);
+					return Ok(());
+				}
+			}
 
-			if create_root {
+			// Directory tagged by "CACHEDIR.TAG" or a marker file.
+			let tag = settings.find_exclusion_tag(path);
+			let keep_tag_file = settings.keep_tag_files == Some(true);
+			if let Some(tag) = &tag {
+				println!("[INFO] IGNORE {} (tagged by {})", shown, tag);
+				self.skip(&internal_path, true);
+				if !keep_tag_file {
+					return Ok(());
+				}
+			}
+
+			// Directories with the same name after normalization are merged.
+			if create_root && !self.is_duplicate(&format!("{}/", internal_path), path) {
+				self.record_raw_path(base_name, &internal_path, path);
+				self.portability.check(&internal_path, name, &self.display(&internal_path))?;
 				let meta = unknown.metadata()?;
 
 				// Create directory attributes.
-				let options = create_file_attributes(&meta)?;
+				let options = self.create_attributes(&internal_path, &meta, zip::CompressionMethod::Stored, None)?;
 
 				// Relative path from the root. "path/to/name"
 				let internal_path = format!("{}/", internal_path);
 
-				println!("  adding: {} (stored)", &internal_path);
+				let shown_path = self.display(&internal_path);
+				println!("  adding: {} (stored)", shown_path);
+
+				// Create directory node. It is written like a file to carry extra fields.
+				let raw = self.raw_name(&internal_path);
+				let (local_extra, central_extra) = self.create_extra_fields(path, &internal_path, &meta, raw.as_ref())?;
+				let mode = self.entry_mode(&meta).unwrap_or(0o755) & 0o777;
+				if let Some(archiver) = self.archiver.as_mut() {
+					archiver.start_file_with_extra_data(&internal_path, options)?;
+					archiver.write_all(&local_extra)?;
+					archiver.end_local_start_central_extra_data()?;
+					archiver.write_all(&central_extra)?;
+					archiver.end_extra_data()?;
+					self.directories.insert(internal_path.clone(), mode);
+				}
+				self.rename_if_needed(&internal_path, raw)?;
+				let (mtime, mode) = (self.entry_time(&meta)?, self.entry_mode(&meta));
+				if let Some(manifest) = self.manifest.as_mut() {
+					manifest.record(manifest::ManifestEntry::new(&shown_path, &meta, mtime, mode, None));
+				}
+			}
+
+			// Only the tag file is kept in the tagged directory. A marker which is a directory is not archived.
+			if let Some(tag) = &tag {
+				let tag_path = path.join(tag);
+				if !tag_path.is_file() {
+					return Ok(());
+				}
+				return self.append(&internal_path, &tag_path, settings, true);
+			}
+
+			// Limit of depth.
+			if let Some(max_depth) = self.filter.max_depth {
+				if max_depth <= self.depth {
+					println!("[INFO] IGNORE contents of {} (deeper than max_depth)", shown);
+					self.skip(&internal_path, true);
+					return Ok(());
+				}
+			}
 
-				// Create directory node.
-				self.archiver.add_directory(&internal_path, options)?;
+			// Patterns in ".rzipignore" apply to the subtree.
+			let ignore = configuration::IgnoreFile::load(path)?;
+			let has_ignore = ignore.is_some();
+			if let Some(ignore) = ignore {
+				self.ignores.push((internal_path.clone(), ignore));
 			}
 
 			// enumerate sub entries.
-			let it = std::fs::read_dir(path)?;
-			for e in it {
-				let entry = e?;
-				let fullpath = entry.path_as_string();
-				self.append(&internal_path, &fullpath, &settings, true)?;
+			self.depth += 1;
+			let mut entries = std::fs::read_dir(path)?.collect::<Result<Vec<std::fs::DirEntry>, std::io::Error>>()?;
+			if let Some(order) = self.sort_order {
+				sort_entries(&mut entries, order);
+			}
+			for entry in entries {
+				self.append(&internal_path, &entry.path(), settings, true)?;
+			}
+			self.depth -= 1;
+
+			if has_ignore {
+				self.ignores.pop();
 			}
 		} else if unknown.is_file() {
 			// name of file
-			let name = unknown.name_as_str();
+			let name = match self.entry_name(unknown) {
+				None => return Ok(()),
+				Some(name) => name,
+			};
+			let name = name.as_str();
+			let shown = names::escape(unknown.file_name().unwrap_or(unknown.as_os_str()));
+			// Relative path from the root. "path/to/name"
+			let internal_path = util::build_archive_internal_path(base_name, name, self.name_normalization);
+
 			// validate its name
-			if !settings.is_valid_filename(name)? {
-				println!("[INFO] IGNORE {}", name);
+			if !settings.is_valid_filename(name)? || self.is_ignored(&internal_path, name, false) {
+				println!("[INFO] IGNORE {}", shown);
+				self.skip(&internal_path, false);
 				return Ok(());
 			}
 
 			let meta = unknown.metadata()?;
 
-			// Create file attributes.
-			let options = create_directory_attributes(&meta)?;
+			// Filters on metadata.
+			if let Some(reason) = self.filter.check_file(&meta)? {
+				println!("[INFO] IGNORE {} ({})", shown, reason);
+				self.skip(&internal_path, false);
+				return Ok(());
+			}
 
-			// Relative path from the root. "path/to/name"
-			let internal_path = util::build_archive_internal_path(base_name, name);
+			// Files with the same name after normalization. The first one is stored.
+			if self.is_duplicate(&internal_path, path) {
+				return Ok(());
+			}
+			self.record_raw_path(base_name, &internal_path, path);
+			let shown_path = self.display(&internal_path);
+			self.portability.check(&internal_path, name, &shown_path)?;
 
-			println!("  adding: {} (deflated)", &internal_path);
+			// Unchanged since the previous run.
+			if let Some(incremental) = self.incremental.as_mut() {
+				if let Some(state) = incremental.find_unchanged(&internal_path, path, &meta)? {
+					println!("[INFO] UNCHANGED {}", shown);
+					incremental.record(&internal_path, &shown_path, state);
+					return Ok(());
+				}
+			}
+
+			// Hard link to a file stored already.
+			let inode = get_inode(&meta);
+			if let Some(target) = inode.and_then(|e| self.hard_links.get(&e)).cloned() {
+				self.linked_files.0 += 1;
+				self.linked_files.1 += meta.len();
+				if self.store_hard_links {
+					return self.append_hard_link(&internal_path, &target, &meta);
+				}
+			}
+
+			// Create file attributes.
+			let method = settings.compression_method()?;
+			let options = self.create_attributes(&internal_path, &meta, method, settings.compression_level)?;
+
+			println!("  adding: {} ({})", shown_path, method.to_string().to_lowercase());
 
 			// Create file node.
-			self.archiver.start_file(&internal_path, options)?;
+			if self.archiver.is_none() {
+				if let Some(incremental) = self.incremental.as_mut() {
+					incremental.record(&internal_path, &shown_path, incremental::FileState::new(&meta, "")?);
+				}
+				if let Some(inode) = inode {
+					self.hard_links.entry(inode).or_insert((internal_path, String::new()));
+				}
+				return Ok(());
+			}
+			let raw = self.raw_name(&internal_path);
+			let (local_extra, central_extra) = self.create_extra_fields(path, &internal_path, &meta, raw.as_ref())?;
+			let archiver = self.archiver.as_mut().unwrap();
+			archiver.start_file_with_extra_data(&internal_path, options)?;
+			archiver.write_all(&local_extra)?;
+			archiver.end_local_start_central_extra_data()?;
+			archiver.write_all(&central_extra)?;
+			archiver.end_extra_data()?;
+			self.rename_if_needed(&internal_path, raw)?;
+			let archiver = self.archiver.as_mut().unwrap();
+			let mut hasher = sha2::Sha256::new();
 			let mut stream = std::fs::File::open(path)?;
 			loop {
 				let mut buffer = [0; 4000];
@@ -99,10 +897,23 @@ impl ZipArchiver {
 					break;
 				}
 				let write_buffer = &buffer[..bytes_read];
-				self.archiver.write_all(&write_buffer)?;
+				archiver.write_all(write_buffer)?;
+				hasher.update(write_buffer);
+			}
+
+			let sha256 = util::to_hex(&hasher.finalize());
+			if let Some(inode) = inode {
+				self.hard_links.entry(inode).or_insert((internal_path.clone(), sha256.clone()));
+			}
+			if let Some(incremental) = self.incremental.as_mut() {
+				incremental.record(&internal_path, &shown_path, incremental::FileState::new(&meta, &sha256)?);
+			}
+			let (mtime, mode) = (self.entry_time(&meta)?, self.entry_mode(&meta));
+			if let Some(manifest) = self.manifest.as_mut() {
+				manifest.record(manifest::ManifestEntry::new(&shown_path, &meta, mtime, mode, Some(sha256)));
 			}
 		} else {
-			let message = format!("Unknown filesystem [{}].", path);
+			let message = format!("Unknown filesystem [{}].", path.display());
 			return Err(message.into());
 		}
 
@@ -112,12 +923,145 @@ impl ZipArchiver {
 
 impl Drop for ZipArchiver {
 	fn drop(&mut self) {
-		let _ = self.archiver.finish();
+		if let Some(archiver) = self.archiver.as_mut() {
+			let _ = archiver.finish();
+		}
+	}
+}
+
+/// Mark directory entries written like files as directories, in the central directory of the finished archive.
+///
+/// # Arguments
+/// * `path_to_archive` Path to the archive.
+/// * `directories` Names of the directories and their permissions.
+fn mark_directories(path_to_archive: &str, directories: &std::collections::HashMap<String, u32>) -> Result<(), Box<dyn std::error::Error>> {
+	use std::io::{Seek, Write};
+
+	let mut positions: Vec<(u64, u32)> = Vec::new();
+	let mut archive = zip::ZipArchive::new(std::fs::File::open(path_to_archive)?)?;
+	for index in 0..archive.len() {
+		let entry = archive.by_index_raw(index)?;
+		if let Some(mode) = directories.get(entry.name()) {
+			positions.push((entry.central_header_start(), *mode));
+		}
+	}
+
+	// External file attributes of the central directory header. (Unix mode in the upper 16 bits)
+	let mut file = std::fs::OpenOptions::new().write(true).open(path_to_archive)?;
+	for (position, mode) in positions {
+		file.seek(std::io::SeekFrom::Start(position + 38))?;
+		file.write_all(&((0o040000 | mode) << 16).to_le_bytes())?;
+	}
+	return Ok(());
+}
+
+/// Sort entries in a directory. Ties are ordered by name.
+fn sort_entries(entries: &mut [std::fs::DirEntry], order: configuration::SortOrder) {
+	match order {
+		configuration::SortOrder::Lexical => entries.sort_by_key(|e| e.file_name()),
+		// Metadata is read once for each entry, not on every comparison.
+		configuration::SortOrder::DirsFirst => entries.sort_by_cached_key(|e| (!e.path().is_dir(), e.file_name())),
+		configuration::SortOrder::Size => entries.sort_by_cached_key(|e| (std::fs::metadata(e.path()).map(|meta| meta.len()).unwrap_or(0), e.file_name())),
+		configuration::SortOrder::Extension => entries.sort_by_key(|e| {
+			let extension = e.path().extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+			return (extension, e.file_name());
+		}),
+	}
+}
+
+/// Read extended attributes of a file. Attributes which do not fit in the extra data are skipped with a warning.
+///
+/// # Arguments
+/// * `path` Path to the file.
+/// * `name` Name of the entry. For warnings.
+/// * `room` Size available for the field.
+///
+/// # Returns
+/// Extended attributes field, or empty if the file has no attributes.
+#[cfg(unix)]
+fn read_xattrs(path: &std::path::Path, name: &str, room: usize) -> Vec<u8> {
+	use std::os::unix::ffi::OsStrExt;
+
+	let mut attribute_names = match xattr::list(path) {
+		Err(error) => {
+			println!("[WARN] Extended attributes of {} cannot be read. ({})", name, error);
+			return Vec::new();
+		}
+		Ok(list) => list.collect::<Vec<std::ffi::OsString>>(),
+	};
+	attribute_names.sort();
+
+	// Header and version
+	let mut size = 5;
+	let mut attributes: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+	for attribute_name in attribute_names {
+		let value = match xattr::get(path, &attribute_name) {
+			Err(error) => {
+				println!(
+					"[WARN] Extended attribute {} of {} cannot be read. ({})",
+					attribute_name.to_string_lossy(),
+					name,
+					error
+				);
+				continue;
+			}
+			Ok(None) => continue,
+			Ok(Some(value)) => value,
+		};
+		let attribute_name = attribute_name.as_bytes().to_vec();
+		let attribute_size = extra_field::xattr_size(&attribute_name, &value);
+		if 255 < attribute_name.len() || room < size + attribute_size {
+			println!(
+				"[WARN] Extended attribute {} of {} is too large to store.",
+				String::from_utf8_lossy(&attribute_name),
+				name
+			);
+			continue;
+		}
+		size += attribute_size;
+		attributes.push((attribute_name, value));
+	}
+
+	if attributes.is_empty() {
+		return Vec::new();
+	}
+	return extra_field::xattrs(&attributes);
+}
+
+/// Device of the file system containing the entry.
+fn get_device(meta: &std::fs::Metadata) -> Option<u64> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::MetadataExt;
+		return Some(meta.dev());
+	}
+
+	#[cfg(not(unix))]
+	{
+		let _ = meta;
+		return None;
+	}
+}
+
+/// Device and inode of a file with hard links. `None` if the file has no other links.
+fn get_inode(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::MetadataExt;
+		if meta.nlink() < 2 {
+			return None;
+		}
+		return Some((meta.dev(), meta.ino()));
+	}
+
+	#[cfg(not(unix))]
+	{
+		let _ = meta;
+		return None;
 	}
 }
 
 /// Retrieve unix permissions as u8
-#[allow(unused)]
 fn get_unix_permissions_as_u8(meta: &std::fs::Metadata) -> Option<u32> {
 	#[cfg(unix)]
 	{
@@ -127,43 +1071,153 @@ fn get_unix_permissions_as_u8(meta: &std::fs::Metadata) -> Option<u32> {
 		return Some(mode);
 	}
 
-	return None;
+	#[cfg(not(unix))]
+	{
+		let _ = meta;
+		return None;
+	}
 }
 
-/// Create file attributes.
-fn create_file_attributes(meta: &std::fs::Metadata) -> Result<zip::write::FileOptions, Box<dyn std::error::Error>> {
-	use crate::util::SystemTimeHelper;
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-	let options = zip::write::FileOptions::default();
+	/// Create an empty directory for a test.
+	fn create_test_dir(name: &str) -> std::path::PathBuf {
+		let path = std::env::temp_dir().join(format!("rzip-test-{}-{}", name, std::process::id()));
+		let _ = std::fs::remove_dir_all(&path);
+		std::fs::create_dir_all(&path).unwrap();
+		return path;
+	}
 
-	// compression method
-	let options = options.compression_method(zip::CompressionMethod::Stored);
+	/// Write an entry with the options into an archive in memory, and read it back.
+	///
+	/// # Returns
+	/// Unix mode and compression method of the entry.
+	fn write_and_read(name: &str, options: zip::write::FileOptions, is_dir: bool) -> (Option<u32>, zip::CompressionMethod) {
+		use std::io::Write;
 
-	// last modified time
-	let last_modified = meta.modified()?.as_ziptime();
-	let options = options.last_modified_time(last_modified);
+		let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+		if is_dir {
+			writer.add_directory(name, options).unwrap();
+		} else {
+			writer.start_file(name, options).unwrap();
+			writer.write_all(b"content").unwrap();
+		}
+		let buffer = writer.finish().unwrap();
 
-	return Ok(options);
-}
+		let mut archive = zip::ZipArchive::new(buffer).unwrap();
+		let entry = archive.by_index(0).unwrap();
+		return (entry.unix_mode(), entry.compression());
+	}
+
+	/// Map the metadata of `path` and write it as an entry.
+	#[cfg(unix)]
+	fn archive_entry(
+		settings: &configuration::Settings,
+		path: &std::path::Path,
+		mode: u32,
+		method: zip::CompressionMethod,
+		level: Option<i32>,
+	) -> (Option<u32>, zip::CompressionMethod) {
+		use std::os::unix::fs::PermissionsExt;
+
+		std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+		let meta = path.metadata().unwrap();
+		let archiver = ZipArchiver::dry_run(settings).unwrap();
+		let options = archiver.create_attributes("entry", &meta, method, level).unwrap();
+		return write_and_read("entry", options, meta.is_dir());
+	}
 
-/// Create directory attributes.
-fn create_directory_attributes(meta: &std::fs::Metadata) -> Result<zip::write::FileOptions, Box<dyn std::error::Error>> {
-	use crate::util::SystemTimeHelper;
+	#[test]
+	#[cfg(unix)]
+	fn directory_keeps_mode_bits() {
+		use std::os::unix::fs::PermissionsExt;
 
-	let options = zip::write::FileOptions::default();
+		let dir = create_test_dir("directory");
+		let source = dir.join("source");
+		std::fs::create_dir(&source).unwrap();
+		std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o750)).unwrap();
+		let path_to_archive = dir.join("test.zip");
+		let settings = configuration::Settings::default();
 
-	// compression method
-	let options = options.compression_method(zip::CompressionMethod::Deflated);
+		// Directories are written with extra fields, and marked after finish.
+		let mut archiver = ZipArchiver::new(path_to_archive.to_str().unwrap(), &settings).unwrap();
+		archiver.append("", &source, &settings, true).unwrap();
+		archiver.finish().unwrap();
 
-	// last modified time
-	let last_modified = meta.modified()?.as_ziptime();
-	let options = options.last_modified_time(last_modified);
+		let mut archive = zip::ZipArchive::new(std::fs::File::open(&path_to_archive).unwrap()).unwrap();
+		let entry = archive.by_index(0).unwrap();
+		assert_eq!(entry.name(), "source/");
+		assert!(entry.is_dir());
+		assert_eq!(entry.unix_mode(), Some(0o040750));
+		assert_eq!(entry.compression(), zip::CompressionMethod::Stored);
+		assert!(extra_field::find(entry.extra_data(), extra_field::EXTENDED_TIMESTAMP).is_some());
+		drop(entry);
+		drop(archive);
 
-	// permissions
-	let options = match get_unix_permissions_as_u8(&meta) {
-		None => options,
-		Some(n) => options.unix_permissions(n),
-	};
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
 
-	return Ok(options);
+	#[test]
+	#[cfg(unix)]
+	fn regular_file_keeps_mode_and_method() {
+		let dir = create_test_dir("regular");
+		let path = dir.join("file.txt");
+		std::fs::write(&path, "content").unwrap();
+		let settings = configuration::Settings::default();
+
+		let (mode, method) = archive_entry(&settings, &path, 0o640, zip::CompressionMethod::Deflated, Some(9));
+		assert_eq!(mode, Some(0o100640));
+		assert_eq!(method, zip::CompressionMethod::Deflated);
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn executable_file_keeps_executable_bit() {
+		let dir = create_test_dir("executable");
+		let path = dir.join("run.sh");
+		std::fs::write(&path, "#!/bin/sh\n").unwrap();
+		let settings = configuration::Settings::default();
+
+		let (mode, _) = archive_entry(&settings, &path, 0o750, zip::CompressionMethod::Deflated, None);
+		assert_eq!(mode, Some(0o100750));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn reproducible_mode_normalizes_permissions() {
+		let dir = create_test_dir("reproducible");
+		let path = dir.join("run.sh");
+		std::fs::write(&path, "#!/bin/sh\n").unwrap();
+		let mut settings = configuration::Settings::default();
+		settings.reproducible = Some(true);
+
+		let (mode, _) = archive_entry(&settings, &path, 0o700, zip::CompressionMethod::Deflated, None);
+		assert_eq!(mode, Some(0o100755));
+		let (mode, _) = archive_entry(&settings, &path, 0o600, zip::CompressionMethod::Deflated, None);
+		assert_eq!(mode, Some(0o100644));
+		let (mode, _) = archive_entry(&settings, &dir, 0o700, zip::CompressionMethod::Stored, None);
+		assert_eq!(mode, Some(0o040755));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn stored_file_ignores_compression_level() {
+		let dir = create_test_dir("stored");
+		let path = dir.join("file.txt");
+		std::fs::write(&path, "content").unwrap();
+		let settings = configuration::Settings::default();
+
+		let (_, method) = archive_entry(&settings, &path, 0o644, zip::CompressionMethod::Stored, Some(6));
+		assert_eq!(method, zip::CompressionMethod::Stored);
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
 }
@@ -4,6 +4,7 @@
 
 extern crate serde_derive;
 
+use crate::manifest;
 use crate::util;
 
 /// Fix "some" to "^some". "*some" is not changed.
@@ -33,24 +34,321 @@ fn make_name_filter(wildcard: &str) -> String {
 	return wildcard;
 }
 
-fn get_env(name: &str) -> String {
-	let value = std::env::var(name);
-	if value.is_err() {
-		return "".to_string();
+/// Exclusion presets. (name, dirs, files)
+const PRESETS: &[(&str, &[&str], &[&str])] = &[
+	("rust", &["target"], &["*.rs.bk"]),
+	(
+		"node",
+		&["node_modules", ".npm", ".next", ".nuxt", ".parcel-cache", ".turbo"],
+		&["npm-debug.log", "yarn-debug.log", "yarn-error.log"],
+	),
+	(
+		"python",
+		&["__pycache__", ".venv", "venv", ".tox", ".nox", ".pytest_cache", ".mypy_cache", ".ruff_cache"],
+		&["*.pyc", "*.pyo"],
+	),
+	("dotnet", &["bin", "obj", ".vs"], &["*.user", "*.suo"]),
+	("java", &[".gradle", "build", "target", "out"], &["*.class"]),
+	("editor", &[".idea", ".vscode", ".vs"], &["*.swp", "*.swo", "*~", "*.sublime-workspace"]),
+	(
+		"os-junk",
+		&["__MACOSX", ".Spotlight-V100", ".Trashes", ".fseventsd", "$RECYCLE.BIN"],
+		&[".DS_Store", "._*", "Thumbs.db", "ehthumbs.db", "desktop.ini"],
+	),
+];
+
+/// Names of the presets.
+fn preset_names() -> String {
+	let names: Vec<&str> = PRESETS.iter().map(|e| e.0).collect();
+	return names.join(", ");
+}
+
+/// Find a preset by name.
+fn find_preset(name: &str) -> Option<&'static (&'static str, &'static [&'static str], &'static [&'static str])> {
+	return PRESETS.iter().find(|e| e.0 == name);
+}
+
+/// Lists which can be discarded by `reset`.
+const RESETTABLE_KEYS: [&str; 5] = ["exclude_dirs", "exclude_files", "exclude_markers", "presets", "jobs"];
+
+/// Name of the cache directory tag. See https://bford.info/cachedir/
+const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+
+/// Signature at the beginning of "CACHEDIR.TAG".
+const CACHEDIR_TAG_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Returns `true` if `path` is a valid "CACHEDIR.TAG".
+fn is_cachedir_tag(path: &std::path::Path) -> bool {
+	use std::io::Read;
+
+	let file = std::fs::File::open(path);
+	if file.is_err() {
+		return false;
+	}
+	let mut buffer = vec![0u8; CACHEDIR_TAG_SIGNATURE.len()];
+	if file.unwrap().read_exact(&mut buffer).is_err() {
+		return false;
+	}
+	return buffer == CACHEDIR_TAG_SIGNATURE;
+}
+
+///
+/// Order of the entries in a directory.
+///
+#[derive(std::fmt::Debug, std::clone::Clone, std::marker::Copy, std::cmp::PartialEq)]
+pub enum SortOrder {
+	/// By name.
+	Lexical,
+
+	/// Directories first, then by name.
+	DirsFirst,
+
+	/// By size, then by name.
+	Size,
+
+	/// By extension, then by name.
+	Extension,
+}
+
+///
+/// Handling of timestamps out of the DOS date range. (1980-01-01 to 2107-12-31)
+///
+#[derive(std::fmt::Debug, std::clone::Clone, std::marker::Copy, std::cmp::PartialEq)]
+pub enum TimePolicy {
+	/// Store the nearest bound.
+	Clamp,
+
+	/// Store the current time.
+	Now,
+
+	/// Fail.
+	Error,
+}
+
+///
+/// Handling of file names which are not valid UTF-8.
+///
+#[derive(std::fmt::Debug, std::clone::Clone, std::marker::Copy, std::cmp::PartialEq)]
+pub enum NamePolicy {
+	/// Skip the entry.
+	Skip,
+
+	/// Store invalid bytes as `%XX`.
+	Escape,
+
+	/// Store the bytes as they are, without the UTF-8 flag.
+	Raw,
+}
+
+///
+/// Unicode normalization of the names in archives.
+///
+#[derive(std::fmt::Debug, std::clone::Clone, std::marker::Copy, std::cmp::PartialEq)]
+pub enum NameNormalization {
+	/// Keep the names as they are.
+	None,
+
+	/// Composed form. (Windows, Linux)
+	Nfc,
+
+	/// Decomposed form. (macOS)
+	Nfd,
+}
+
+///
+/// Encoding of the names in archives.
+///
+#[derive(std::fmt::Debug, std::clone::Clone, std::marker::Copy, std::cmp::PartialEq)]
+pub enum NameEncoding {
+	/// UTF-8 with the language encoding flag.
+	Utf8,
+
+	/// Shift_JIS. (Windows code page 932)
+	Cp932,
+
+	/// IBM PC. (code page 437)
+	Cp437,
+}
+
+impl NameEncoding {
+	/// Parse the name of the encoding. (`utf8`, `cp932`, `cp437`)
+	pub fn parse(name: &str) -> Result<NameEncoding, Box<dyn std::error::Error>> {
+		let encoding = match name.to_lowercase().as_str() {
+			"utf8" | "utf-8" => NameEncoding::Utf8,
+			"cp932" | "sjis" | "shift_jis" => NameEncoding::Cp932,
+			"cp437" => NameEncoding::Cp437,
+			_ => return Err(format!("Unknown name encoding [{}]. (utf8, cp932, cp437)", name).into()),
+		};
+		return Ok(encoding);
+	}
+}
+
+///
+/// Filters evaluated on metadata of entries.
+///
+#[derive(std::default::Default)]
+pub struct EntryFilter {
+	/// Exclude files smaller than this.
+	min_size: Option<u64>,
+
+	/// Exclude files larger than this.
+	max_size: Option<u64>,
+
+	/// Exclude files modified before this.
+	newer_than: Option<std::time::SystemTime>,
+
+	/// Exclude files modified after this.
+	older_than: Option<std::time::SystemTime>,
+
+	/// Do not descend deeper than this below the source.
+	pub max_depth: Option<usize>,
+
+	/// Description of the filters.
+	descriptions: Vec<String>,
+}
+
+impl EntryFilter {
+	/// Descriptions of the active filters. (e.g. `max_size = 10M`)
+	pub fn describe(&self) -> &Vec<String> {
+		return &self.descriptions;
+	}
+
+	/// Check metadata of a file.
+	///
+	/// # Returns
+	/// Reason to exclude the file, or `None` if it is included.
+	pub fn check_file(&self, meta: &std::fs::Metadata) -> Result<Option<String>, Box<dyn std::error::Error>> {
+		let size = meta.len();
+		if let Some(min_size) = self.min_size {
+			if size < min_size {
+				return Ok(Some(format!("smaller than min_size, {} bytes", size)));
+			}
+		}
+		if let Some(max_size) = self.max_size {
+			if max_size < size {
+				return Ok(Some(format!("larger than max_size, {} bytes", size)));
+			}
+		}
+
+		if self.newer_than.is_none() && self.older_than.is_none() {
+			return Ok(None);
+		}
+		let modified = meta.modified()?;
+		if let Some(newer_than) = self.newer_than {
+			if modified < newer_than {
+				return Ok(Some("not newer than newer_than".to_string()));
+			}
+		}
+		if let Some(older_than) = self.older_than {
+			if older_than < modified {
+				return Ok(Some("not older than older_than".to_string()));
+			}
+		}
+
+		return Ok(None);
+	}
+}
+
+/// Name of the per-directory ignore file.
+pub const IGNORE_FILE_NAME: &str = ".rzipignore";
+
+///
+/// A pattern in `.rzipignore`
+///
+struct IgnoreRule {
+	/// Compiled pattern.
+	regex: regex::Regex,
+
+	/// Pattern ends with "/". Matches only directories.
+	dir_only: bool,
+
+	/// Pattern contains "/". Matches the relative path from the directory of `.rzipignore`.
+	with_path: bool,
+}
+
+///
+/// Patterns in `.rzipignore`, which apply to the subtree of its directory.
+///
+pub struct IgnoreFile {
+	/// Patterns.
+	rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreFile {
+	/// Read `.rzipignore` in the directory.
+	///
+	/// # Arguments
+	/// * `dir` Path to the directory.
+	///
+	/// # Returns
+	/// `None` if the directory has no `.rzipignore`.
+	pub fn load(dir: &std::path::Path) -> Result<Option<IgnoreFile>, Box<dyn std::error::Error>> {
+		let path = dir.join(IGNORE_FILE_NAME);
+		if !path.is_file() {
+			return Ok(None);
+		}
+
+		let content = std::fs::read_to_string(&path)?;
+		let mut rules: Vec<IgnoreRule> = Vec::new();
+		for (index, line) in content.lines().enumerate() {
+			let line = line.trim();
+			if line == "" || line.starts_with('#') {
+				continue;
+			}
+
+			// "name/" matches only directories.
+			let dir_only = line.ends_with('/');
+			let pattern = line.trim_end_matches('/');
+
+			// "/name" and "path/to/name" are relative to the directory of `.rzipignore`.
+			let with_path = pattern.contains('/');
+			let pattern = pattern.trim_start_matches('/');
+
+			let regex = regex::Regex::new(&make_name_filter(pattern));
+			if regex.is_err() {
+				return Err(format!("Invalid pattern in [{}:{}]. reason: {}", path.display(), index + 1, regex.err().unwrap()).into());
+			}
+
+			rules.push(IgnoreRule {
+				regex: regex.unwrap(),
+				dir_only,
+				with_path,
+			});
+		}
+
+		return Ok(Some(IgnoreFile { rules }));
+	}
+
+	/// Returns `true` if the entry is excluded.
+	///
+	/// # Arguments
+	/// * `relative` Relative path from the directory of `.rzipignore`. "path/to/name"
+	/// * `name` Name of the entry.
+	/// * `is_dir` The entry is a directory.
+	pub fn is_excluded(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+		for rule in &self.rules {
+			if rule.dir_only && !is_dir {
+				continue;
+			}
+			let target = if rule.with_path { relative } else { name };
+			if rule.regex.is_match(target) {
+				return true;
+			}
+		}
+		return false;
 	}
-	return value.unwrap();
 }
 
 /// Detect the user's home directory.
 fn detect_users_home_dir() -> String {
 	// (Windows) Detect the user's home directory.
-	let home = get_env("USERPROFILE");
+	let home = util::get_env("USERPROFILE");
 	if home != "" {
 		return home;
 	}
 
 	// (Linux) Detect the user's home directory.
-	let home = get_env("HOME");
+	let home = util::get_env("HOME");
 	if home != "" {
 		return home;
 	}
@@ -58,54 +356,552 @@ fn detect_users_home_dir() -> String {
 	return "".to_string();
 }
 
-/// Detect "settings.toml" in the current directory or the user's home directory.
-fn find_settings_toml() -> Result<String, Box<dyn std::error::Error>> {
-	const NAME: &str = "settings.toml";
+/// Append `path` to `files` if it exists.
+fn push_if_exists(files: &mut Vec<String>, path: &str) {
+	if path == "" {
+		return;
+	}
+	if std::path::Path::new(path).is_file() {
+		files.push(path.to_string());
+	}
+}
+
+/// Find the nearest ".rzip.toml" walking up from `source`.
+fn find_project_file(source: &std::path::Path) -> Option<String> {
+	const NAME: &str = ".rzip.toml";
 
-	// Detect "settings.toml" in the current directory.
-	if std::path::Path::new(NAME).is_file() {
-		return Ok(NAME.to_string());
+	let path = source.canonicalize().ok()?;
+	let mut dir = if path.is_dir() { Some(path.as_path()) } else { path.parent() };
+	while let Some(current) = dir {
+		let candidate = current.join(NAME);
+		if candidate.is_file() {
+			return Some(candidate.to_str()?.to_string());
+		}
+		dir = current.parent();
+	}
+	return None;
+}
+
+/// Detect configuration files from the lowest priority to the highest.
+///
+/// 1. System: `/etc/rzip/settings.toml` or `%PROGRAMDATA%\rzip\settings.toml`
+/// 2. User: `~/settings.toml`, `$XDG_CONFIG_HOME/rzip/settings.toml` (`%APPDATA%\rzip\settings.toml`), `~/.rziprc`
+/// 3. Current directory: `./settings.toml`
+/// 4. Project: the nearest `.rzip.toml` walking up from the source directory.
+///
+/// # Arguments
+/// * `source` Path to the source. The current directory is used if empty.
+fn find_configuration_files(source: &std::path::Path) -> Vec<String> {
+	let mut files: Vec<String> = Vec::new();
+
+	// System
+	if cfg!(windows) {
+		let program_data = util::get_env("PROGRAMDATA");
+		if program_data != "" {
+			push_if_exists(&mut files, &util::concat_path(&program_data, "rzip\\settings.toml"));
+		}
+	} else {
+		push_if_exists(&mut files, "/etc/rzip/settings.toml");
 	}
 
-	// Detect the user's home directory.
+	// User
 	let home = detect_users_home_dir();
-	if home == "" {
-		return Ok("".to_string());
+	if home != "" {
+		push_if_exists(&mut files, &util::concat_path(&home, "settings.toml"));
+	}
+	let config_home = if cfg!(windows) {
+		util::get_env("APPDATA")
+	} else {
+		util::get_env("XDG_CONFIG_HOME")
+	};
+	let config_home = if config_home == "" && home != "" {
+		util::concat_path(&home, ".config")
+	} else {
+		config_home
+	};
+	if config_home != "" {
+		push_if_exists(&mut files, &util::concat_path(&util::concat_path(&config_home, "rzip"), "settings.toml"));
+	}
+	if home != "" {
+		push_if_exists(&mut files, &util::concat_path(&home, ".rziprc"));
+	}
+
+	// Current directory
+	push_if_exists(&mut files, "settings.toml");
+
+	// Project
+	let source = if source.as_os_str().is_empty() { std::path::Path::new(".") } else { source };
+	if let Some(path) = find_project_file(source) {
+		files.push(path);
+	}
+
+	// The same file may be found twice. (e.g. running in the home directory)
+	let mut unique: Vec<String> = Vec::new();
+	let mut found: Vec<String> = Vec::new();
+	for path in files {
+		let canonical = util::canonicalize_path(&path).unwrap_or_else(|_| path.clone());
+		if found.contains(&canonical) {
+			continue;
+		}
+		found.push(canonical);
+		unique.push(path);
+	}
+
+	return unique;
+}
+
+/// Validate a pattern of file name. Returns the problem if any.
+fn validate_pattern(wildcard: &str) -> Option<String> {
+	let result = regex::Regex::new(&make_name_filter(wildcard));
+	if result.is_err() {
+		return Some(format!("Invalid pattern [{}]. reason: {}", wildcard, result.err().unwrap()));
+	}
+	return None;
+}
+
+/// Validate a directory name. Returns the problem if any.
+fn validate_dir_name(name: &str) -> Option<String> {
+	if name == "" {
+		return Some("Empty directory name.".to_string());
+	}
+	if name.contains('/') || name.contains('\\') {
+		return Some(format!("Directory name [{}] contains a separator. Only names are compared.", name));
+	}
+	return None;
+}
+
+/// Find the line number where `key` is defined.
+///
+/// # Arguments
+/// * `content` Content of settings.toml.
+/// * `key` Dotted key like `compression` or `jobs.web.sources`. A table like `jobs.web` is found by its header.
+fn find_line(content: &str, key: &str) -> Option<usize> {
+	let (table, name) = match key.rfind('.') {
+		Some(pos) => (&key[..pos], &key[pos + 1..]),
+		None => ("", key),
+	};
+	let header = regex::Regex::new(r"^\s*\[\s*([^\[\]]+?)\s*\]").unwrap();
+	let assignment = regex::Regex::new(&format!(r"^\s*{}\s*=", regex::escape(name))).unwrap();
+
+	let mut current = String::new();
+	for (index, line) in content.lines().enumerate() {
+		if let Some(captures) = header.captures(line) {
+			current = captures[1].to_string();
+			if current == key {
+				return Some(index + 1);
+			}
+			continue;
+		}
+		if current == table && assignment.is_match(line) {
+			return Some(index + 1);
+		}
+	}
+	return None;
+}
+
+/// Dotted key of a path reported by [serde_ignored]. (e.g. `jobs.web.colour`)
+fn dotted_key(path: &serde_ignored::Path) -> String {
+	let (parent, key) = match path {
+		serde_ignored::Path::Root => return String::new(),
+		serde_ignored::Path::Map { parent, key } => (parent, key.to_string()),
+		serde_ignored::Path::Seq { parent, index } => (parent, index.to_string()),
+		serde_ignored::Path::Some { parent } | serde_ignored::Path::NewtypeStruct { parent } | serde_ignored::Path::NewtypeVariant { parent } => {
+			return dotted_key(parent);
+		}
+	};
+	let parent = dotted_key(parent);
+	if parent == "" {
+		return key;
 	}
+	return format!("{}.{}", parent, key);
+}
 
-	let path = util::concat_path(&home, NAME);
-	if !std::path::Path::new(&path).is_file() {
-		return Ok("".to_string());
+/// Validate every configuration file. Problems are printed.
+///
+/// # Arguments
+/// * `explicit` Path to a configuration file specified by `--config`.
+/// * `source` Path to the source, to find the project configuration.
+///
+/// # Returns
+/// Number of problems.
+pub fn check(explicit: Option<&str>, source: &std::path::Path) -> Result<usize, Box<dyn std::error::Error>> {
+	let mut files = find_configuration_files(source);
+	if let Some(path) = explicit {
+		if !std::path::Path::new(path).is_file() {
+			return Err(format!("Configuration file not found. [{}]", path).into());
+		}
+		files.push(path.to_string());
 	}
+	if files.is_empty() {
+		println!("[INFO] No configuration files found.");
+		return Ok(0);
+	}
+
+	let mut count = 0;
+	for path in &files {
+		let content = util::read_text_file_all(path)?;
+		let mut unknown: Vec<String> = Vec::new();
+		let result: Result<Settings, _> = serde_ignored::deserialize(&mut toml::Deserializer::new(&content), |key| unknown.push(dotted_key(&key)));
+		if result.is_err() {
+			let error = result.err().unwrap();
+			let line = match error.line_col() {
+				Some((line, _)) => format!("{}:", line + 1),
+				None => "".to_string(),
+			};
+			println!("[ERROR] {}:{} {}", path, line, error);
+			count += 1;
+			continue;
+		}
 
-	return Ok(path);
+		// Unknown keys are ignored on load, but reported here.
+		let mut problems: Vec<(String, String)> = unknown.into_iter().map(|key| (key.clone(), format!("Unknown key [{}].", key))).collect();
+		problems.extend(result.unwrap().validate());
+		for (key, message) in &problems {
+			let line = match find_line(&content, key) {
+				Some(line) => format!("{}:", line),
+				None => "".to_string(),
+			};
+			println!("[ERROR] {}:{} {}", path, line, message);
+		}
+		if problems.is_empty() {
+			println!("[INFO] {}: Ok.", path);
+		}
+		count += problems.len();
+	}
+
+	return Ok(count);
+}
+
+/// Starter configuration file.
+const STARTER: &str = r#"#
+# rzip configuration
+#
+# Configuration files are merged from the lowest priority to the highest.
+#   /etc/rzip/settings.toml, ~/.config/rzip/settings.toml, ~/.rziprc,
+#   ./settings.toml, .rzip.toml (nearest to the source), --config FILE
+# Lists are merged, and other values are overridden.
+#
+
+# Discard lists inherited from the former files.
+# reset = ["exclude_dirs", "exclude_files", "presets", "jobs"]
+
+# Exclude directories tagged with CACHEDIR.TAG or marker files.
+# exclude_caches = true
+# exclude_markers = [".nobackup"]
+# keep_tag_files = false
+
+# Filters on metadata. Times are dates (2024-01-31), durations before now (7d) or paths to files.
+# min_size = "1K"
+# max_size = "100M"
+# newer_than = "7d"
+# older_than = "2024-01-31"
+# max_depth = 3
+
+# Archive only files new or changed since the previous run. (state file or previous archive)
+# incremental = "backup.state.json"
+# record_deletions = true
+
+# Manifest of the entries in the archive (sha256 or json), and {archive}.sha256 next to the archive.
+# manifest = "sha256"
+# checksum_file = true
+
+# Sign the archive with the secret key created by `rzip keygen`. ({archive}.minisig)
+# sign = "/path/to/rzip.key"
+
+# Order of the entries in a directory. (lexical, dirs-first, size, extension)
+# sort = "dirs-first"
+
+# Timestamps out of the DOS date range (1980 to 2107). (clamp, now, error)
+# out_of_range_time = "clamp"
+
+# File names which are not valid UTF-8. (skip, escape, raw)
+# non_utf8_names = "escape"
+
+# Encoding of the names in the archive. (utf8, cp932, cp437)
+# name_encoding = "cp932"
+
+# Unicode normalization of the names. (nfc, nfd, none)
+# normalize_names = "nfc"
+
+# Fail if names break on Windows or macOS. (reserved names, invalid characters, case-insensitive collisions, long paths)
+# portable = true
+
+# Store extended attributes of files. (user.*, security.capability, ...)
+# xattrs = true
+
+# Store hard links once. Later links refer to the first entry.
+# hard_links = true
+
+# Do not descend into directories on other file systems. (mounted shares, bind mounts, ...)
+# one_file_system = true
+
+# Make byte-identical archives from identical inputs. Timestamps are clamped to SOURCE_DATE_EPOCH.
+# reproducible = true
+
+# Exclusion presets. (rust, node, python, dotnet, java, editor, os-junk)
+# presets = ["node", "os-junk"]
+
+# Directory names to exclude.
+exclude_dirs = [
+	".git",
+	"node_modules",
+]
+
+# File names to exclude. "*" matches any characters.
+exclude_files = [
+	# "*.obj",
+]
+
+# Compression method of files. (deflated, stored, bzip2, zstd)
+# compression = "deflated"
+# compression_level = 6
+
+# Retention of archives matching the same naming template.
+# keep_last = 7
+# keep_daily = 7
+# keep_weekly = 4
+# keep_monthly = 12
+# max_age = "90d"
+
+# Named jobs. Run with "rzip run backup-web" or "rzip run --all".
+# [jobs.backup-web]
+# sources = ["/var/www"]
+# destination = "/backup/web-{date:%Y%m%d}.zip"
+# compression = "zstd"
+# exclude_files = ["*.log"]
+# max_size = "100M"
+"#;
+
+/// Write a starter configuration file.
+///
+/// # Arguments
+/// * `path` Path to write. It must not exist.
+pub fn init(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+	use std::io::Write;
+
+	if std::path::Path::new(path).exists() {
+		return Err(format!("File already exists. [{}]", path).into());
+	}
+	let mut file = std::fs::File::create(path)?;
+	file.write_all(STARTER.as_bytes())?;
+	println!("[INFO] Created {}", path);
+	return Ok(());
+}
+
+/// Merge list `value` into `target`.
+fn merge_set(target: &mut Option<std::collections::HashSet<String>>, value: Option<std::collections::HashSet<String>>) {
+	if let Some(value) = value {
+		target.get_or_insert_with(std::collections::HashSet::new).extend(value);
+	}
+}
+
+/// Override `target` by `value` if specified.
+fn override_with<T>(target: &mut Option<T>, value: Option<T>) {
+	if value.is_some() {
+		*target = value;
+	}
 }
 
 ///
 /// Structure for Settings
 ///
-#[derive(serde_derive::Deserialize, std::fmt::Debug, std::clone::Clone)]
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, std::fmt::Debug, std::clone::Clone, std::default::Default)]
 pub struct Settings {
 	/// Dirs to exclude.
 	pub exclude_dirs: Option<std::collections::HashSet<String>>,
 
 	/// Files to exclude.
 	pub exclude_files: Option<std::collections::HashSet<String>>,
+
+	/// Retention: Keep the last N archives.
+	pub keep_last: Option<usize>,
+
+	/// Retention: Keep the last archive of N days.
+	pub keep_daily: Option<usize>,
+
+	/// Retention: Keep the last archive of N weeks.
+	pub keep_weekly: Option<usize>,
+
+	/// Retention: Keep the last archive of N months.
+	pub keep_monthly: Option<usize>,
+
+	/// Retention: Remove archives older than this. (e.g. `30d`)
+	pub max_age: Option<String>,
+
+	/// Compression method of files. (`deflated`, `stored`, `bzip2`, `zstd`)
+	pub compression: Option<String>,
+
+	/// Compression level.
+	pub compression_level: Option<i32>,
+
+	/// Exclude directories containing a valid "CACHEDIR.TAG".
+	pub exclude_caches: Option<bool>,
+
+	/// Exclude directories containing one of these files. (e.g. `.nobackup`)
+	pub exclude_markers: Option<std::collections::HashSet<String>>,
+
+	/// Keep the tag file in the excluded directory.
+	pub keep_tag_files: Option<bool>,
+
+	/// Exclude files smaller than this. (e.g. `1K`)
+	pub min_size: Option<String>,
+
+	/// Exclude files larger than this. (e.g. `10M`)
+	pub max_size: Option<String>,
+
+	/// Exclude files modified before this. (date, duration before now or path to a file)
+	pub newer_than: Option<String>,
+
+	/// Exclude files modified after this. (date, duration before now or path to a file)
+	pub older_than: Option<String>,
+
+	/// Do not descend deeper than this below the source.
+	pub max_depth: Option<usize>,
+
+	/// Archive only files new or changed since the state in this file. (state file or previous archive)
+	pub incremental: Option<String>,
+
+	/// Write files deleted since the previous run into the archive.
+	pub record_deletions: Option<bool>,
+
+	/// Write a manifest of the entries into the archive. (`sha256` or `json`)
+	pub manifest: Option<String>,
+
+	/// Write SHA-256 of the archive next to it. (`{archive}.sha256`)
+	pub checksum_file: Option<bool>,
+
+	/// Sign the archive with the secret key in this file. (`{archive}.minisig`)
+	pub sign: Option<String>,
+
+	/// Order of the entries in a directory. (`lexical`, `dirs-first`, `size`, `extension`)
+	pub sort: Option<String>,
+
+	/// Handling of timestamps out of the DOS date range. (`clamp`, `now`, `error`)
+	pub out_of_range_time: Option<String>,
+
+	/// Handling of file names which are not valid UTF-8. (`skip`, `escape`, `raw`)
+	pub non_utf8_names: Option<String>,
+
+	/// Encoding of the names in the archive. (`utf8`, `cp932`, `cp437`)
+	pub name_encoding: Option<String>,
+
+	/// Unicode normalization of the names in the archive. (`nfc`, `nfd`, `none`)
+	pub normalize_names: Option<String>,
+
+	/// Names which break on Windows or macOS are errors, not warnings.
+	pub portable: Option<bool>,
+
+	/// Store extended attributes of files.
+	pub xattrs: Option<bool>,
+
+	/// Store hard links once.
+	pub hard_links: Option<bool>,
+
+	/// Do not descend into directories on other file systems.
+	pub one_file_system: Option<bool>,
+
+	/// Make byte-identical archives from identical inputs.
+	pub reproducible: Option<bool>,
+
+	/// Exclusion presets. (`rust`, `node`, `python`, `dotnet`, `java`, `editor`, `os-junk`)
+	pub presets: Option<std::collections::HashSet<String>>,
+
+	/// Named jobs. (`[jobs.name]`)
+	pub jobs: Option<std::collections::BTreeMap<String, JobSettings>>,
+
+	/// Lists inherited from the lower layers to discard. (`exclude_dirs`, `exclude_files`, `exclude_markers`, `presets`, `jobs`)
+	#[serde(skip_serializing)]
+	pub reset: Option<Vec<String>>,
+
+	/// Loaded configuration files. (lowest priority first)
+	#[serde(skip)]
+	pub files: Vec<String>,
+
+	/// Where each value came from. (`key`, `key[item]` or `jobs.name` → path)
+	#[serde(skip)]
+	origins: std::collections::BTreeMap<String, String>,
+}
+
+///
+/// Structure for a named job. (`[jobs.name]` in settings.toml)
+///
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, std::fmt::Debug, std::clone::Clone, std::default::Default)]
+pub struct JobSettings {
+	/// Paths to archive.
+	pub sources: Vec<String>,
+
+	/// Path to a new archive. Naming template is available.
+	pub destination: String,
+
+	/// Create a root directory. (default: `true`)
+	pub create_root: Option<bool>,
+
+	/// Compression method of files. Overrides the global one.
+	pub compression: Option<String>,
+
+	/// Compression level. Overrides the global one.
+	pub compression_level: Option<i32>,
+
+	/// State file or previous archive for incremental archiving. Overrides the global one.
+	pub incremental: Option<String>,
+
+	/// Dirs to exclude in addition to the global ones.
+	pub exclude_dirs: Option<std::collections::HashSet<String>>,
+
+	/// Files to exclude in addition to the global ones.
+	pub exclude_files: Option<std::collections::HashSet<String>>,
+
+	/// Exclusion presets in addition to the global ones.
+	pub presets: Option<std::collections::HashSet<String>>,
+
+	/// Exclude directories containing a valid "CACHEDIR.TAG". Overrides the global one.
+	pub exclude_caches: Option<bool>,
+
+	/// Marker files in addition to the global ones.
+	pub exclude_markers: Option<std::collections::HashSet<String>>,
+
+	/// Keep the tag file in the excluded directory. Overrides the global one.
+	pub keep_tag_files: Option<bool>,
+
+	/// Exclude files smaller than this. Overrides the global one.
+	pub min_size: Option<String>,
+
+	/// Exclude files larger than this. Overrides the global one.
+	pub max_size: Option<String>,
+
+	/// Exclude files modified before this. Overrides the global one.
+	pub newer_than: Option<String>,
+
+	/// Exclude files modified after this. Overrides the global one.
+	pub older_than: Option<String>,
+
+	/// Do not descend deeper than this below the source. Overrides the global one.
+	pub max_depth: Option<usize>,
 }
 
 impl Settings {
-	/// Create a new instance.
-	pub fn new() -> Result<Settings, Box<dyn std::error::Error>> {
+	/// Load layered configuration files.
+	///
+	/// # Arguments
+	/// * `explicit` Path to a configuration file specified by `--config`. It has the highest priority.
+	/// * `source` Path to the source, to find the project configuration.
+	pub fn load(explicit: Option<&str>, source: &std::path::Path) -> Result<Settings, Box<dyn std::error::Error>> {
 		let mut instance = Settings {
 			exclude_dirs: Some(std::collections::HashSet::new()),
 			exclude_files: Some(std::collections::HashSet::new()),
+			..Default::default()
 		};
 
-		// Detect "settings.toml" in the current directory or the user's home directory.
-		let path = find_settings_toml()?;
+		for path in find_configuration_files(source) {
+			instance.configure(&path)?;
+		}
 
-		// Configure
-		instance.configure(&path)?;
+		if let Some(path) = explicit {
+			if !std::path::Path::new(path).is_file() {
+				return Err(format!("Configuration file not found. [{}]", path).into());
+			}
+			instance.configure(path)?;
+		}
 
 		return Ok(instance);
 	}
@@ -115,32 +911,489 @@ impl Settings {
 	/// # Arguments
 	/// * `path` Path to "settings.toml"
 	fn configure(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-		// Skip if path is empty.
-		if path == "" {
-			return Ok(());
+		// テキストファイル全体を読み込み
+		let content = util::read_text_file_all(path)?;
+
+		// toml ファイルをパース
+		let result = toml::from_str::<Settings>(&content);
+		if result.is_err() {
+			return Err(format!("Invalid configuration [{}]. reason: {}", path, result.err().unwrap()).into());
 		}
 
-		// Skip if the file does not exist.
-		if !std::path::Path::new(path).is_file() {
-			println!("[INFO] Configuration file not found. (settings.toml)");
-			return Ok(());
+		self.merge(result.unwrap())?;
+
+		// Remember where the values came from.
+		let value: toml::Value = toml::from_str(&content)?;
+		if let Some(table) = value.as_table() {
+			for (key, value) in table {
+				match value {
+					toml::Value::Array(items) => {
+						for item in items {
+							self.origins.insert(format!("{}[{}]", key, item.as_str().unwrap_or_default()), path.to_string());
+						}
+					}
+					toml::Value::Table(children) => {
+						for name in children.keys() {
+							self.origins.insert(format!("{}.{}", key, name), path.to_string());
+						}
+					}
+					_ => {
+						self.origins.insert(key.to_string(), path.to_string());
+					}
+				}
+			}
 		}
+		self.files.push(path.to_string());
 
-		// テキストファイル全体を読み込み
-		let content = util::read_text_file_all(&path)?;
+		return Ok(());
+	}
 
-		// toml ファイルをパース
-		*self = toml::from_str(&content)?;
-		if self.exclude_dirs.is_none() {
-			self.exclude_dirs = Some(std::collections::HashSet::new());
+	/// Record that `key` is specified by `origin`. (e.g. command line)
+	pub fn set_origin(&mut self, key: &str, origin: &str) {
+		self.origins.insert(key.to_string(), origin.to_string());
+	}
+
+	/// Merge a higher priority layer. Lists are merged, and other values are overridden.
+	fn merge(&mut self, layer: Settings) -> Result<(), Box<dyn std::error::Error>> {
+		// Discard inherited lists.
+		for key in layer.reset.iter().flatten() {
+			match key.as_str() {
+				"exclude_dirs" => self.exclude_dirs = Some(std::collections::HashSet::new()),
+				"exclude_files" => self.exclude_files = Some(std::collections::HashSet::new()),
+				"exclude_markers" => self.exclude_markers = None,
+				"presets" => self.presets = None,
+				"jobs" => self.jobs = None,
+				_ => return Err(format!("Unknown key in reset. [{}] ({})", key, RESETTABLE_KEYS.join(", ")).into()),
+			}
+			let prefix = if key == "jobs" { "jobs.".to_string() } else { format!("{}[", key) };
+			self.origins.retain(|e, _| !e.starts_with(&prefix));
 		}
-		if self.exclude_files.is_none() {
-			self.exclude_files = Some(std::collections::HashSet::new());
+
+		merge_set(&mut self.exclude_dirs, layer.exclude_dirs);
+		merge_set(&mut self.exclude_files, layer.exclude_files);
+		merge_set(&mut self.exclude_markers, layer.exclude_markers);
+		merge_set(&mut self.presets, layer.presets);
+		override_with(&mut self.keep_last, layer.keep_last);
+		override_with(&mut self.keep_daily, layer.keep_daily);
+		override_with(&mut self.keep_weekly, layer.keep_weekly);
+		override_with(&mut self.keep_monthly, layer.keep_monthly);
+		override_with(&mut self.max_age, layer.max_age);
+		override_with(&mut self.compression, layer.compression);
+		override_with(&mut self.compression_level, layer.compression_level);
+		override_with(&mut self.exclude_caches, layer.exclude_caches);
+		override_with(&mut self.keep_tag_files, layer.keep_tag_files);
+		override_with(&mut self.incremental, layer.incremental);
+		override_with(&mut self.record_deletions, layer.record_deletions);
+		override_with(&mut self.manifest, layer.manifest);
+		override_with(&mut self.checksum_file, layer.checksum_file);
+		override_with(&mut self.sign, layer.sign);
+		override_with(&mut self.sort, layer.sort);
+		override_with(&mut self.out_of_range_time, layer.out_of_range_time);
+		override_with(&mut self.non_utf8_names, layer.non_utf8_names);
+		override_with(&mut self.name_encoding, layer.name_encoding);
+		override_with(&mut self.normalize_names, layer.normalize_names);
+		override_with(&mut self.portable, layer.portable);
+		override_with(&mut self.xattrs, layer.xattrs);
+		override_with(&mut self.hard_links, layer.hard_links);
+		override_with(&mut self.one_file_system, layer.one_file_system);
+		override_with(&mut self.reproducible, layer.reproducible);
+		override_with(&mut self.min_size, layer.min_size);
+		override_with(&mut self.max_size, layer.max_size);
+		override_with(&mut self.newer_than, layer.newer_than);
+		override_with(&mut self.older_than, layer.older_than);
+		override_with(&mut self.max_depth, layer.max_depth);
+		if let Some(jobs) = layer.jobs {
+			self.jobs.get_or_insert_with(std::collections::BTreeMap::new).extend(jobs);
+		}
+
+		return Ok(());
+	}
+
+	/// Expand presets into `exclude_dirs` and `exclude_files`.
+	pub fn expand_presets(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+		let mut names: Vec<String> = self.presets.iter().flatten().cloned().collect();
+		names.sort();
+
+		for name in &names {
+			let preset = find_preset(name);
+			if preset.is_none() {
+				return Err(format!("Unknown preset [{}]. ({})", name, preset_names()).into());
+			}
+			let (_, dirs, files) = preset.unwrap();
+			let origin = format!("(preset {})", name);
+			for e in dirs.iter() {
+				if self.exclude_dirs.get_or_insert_with(std::collections::HashSet::new).insert(e.to_string()) {
+					self.set_origin(&format!("exclude_dirs[{}]", e), &origin);
+				}
+			}
+			for e in files.iter() {
+				if self.exclude_files.get_or_insert_with(std::collections::HashSet::new).insert(e.to_string()) {
+					self.set_origin(&format!("exclude_files[{}]", e), &origin);
+				}
+			}
 		}
 
 		return Ok(());
 	}
 
+	/// Print the effective settings with the source file of each value.
+	pub fn show(&self) -> Result<(), Box<dyn std::error::Error>> {
+		println!("# Configuration files (lowest priority first)");
+		for path in &self.files {
+			println!("#   {}", path);
+		}
+		if self.files.is_empty() {
+			println!("#   (none)");
+		}
+		println!();
+
+		let value = toml::Value::try_from(self)?;
+		let table = value.as_table().unwrap();
+
+		for (key, value) in table {
+			match value {
+				toml::Value::Array(items) => {
+					let mut items: Vec<String> = items.iter().map(|e| e.as_str().unwrap_or_default().to_string()).collect();
+					items.sort();
+					println!("{} = [", key);
+					for item in &items {
+						let origin = self.origin(&format!("{}[{}]", key, item));
+						println!("\t{}, # {}", toml::Value::String(item.to_string()), origin);
+					}
+					println!("]");
+				}
+				toml::Value::Table(_) => {}
+				_ => println!("{} = {} # {}", key, value, self.origin(key)),
+			}
+		}
+
+		for (key, value) in table {
+			if let toml::Value::Table(children) = value {
+				for (name, child) in children {
+					println!();
+					println!("[{}.{}] # {}", key, name, self.origin(&format!("{}.{}", key, name)));
+					print!("{}", toml::to_string(child)?);
+				}
+			}
+		}
+
+		return Ok(());
+	}
+
+	/// Source of the value.
+	fn origin(&self, key: &str) -> String {
+		return match self.origins.get(key) {
+			Some(path) => path.to_string(),
+			None => "(default)".to_string(),
+		};
+	}
+
+	/// Validate values. Returns problems as (key to locate, message).
+	fn validate(&self) -> Vec<(String, String)> {
+		let mut problems: Vec<(String, String)> = Vec::new();
+
+		for e in self.exclude_dirs.iter().flatten() {
+			problems.extend(validate_dir_name(e).map(|message| ("exclude_dirs".to_string(), message)));
+		}
+		for e in self.exclude_files.iter().flatten() {
+			problems.extend(validate_pattern(e).map(|message| ("exclude_files".to_string(), message)));
+		}
+		if let Err(error) = self.compression_method() {
+			problems.push(("compression".to_string(), error.to_string()));
+		}
+		if let Err(error) = self.time_policy() {
+			problems.push(("out_of_range_time".to_string(), error.to_string()));
+		}
+		if let Err(error) = self.name_policy() {
+			problems.push(("non_utf8_names".to_string(), error.to_string()));
+		}
+		if let Err(error) = self.name_encoding() {
+			problems.push(("name_encoding".to_string(), error.to_string()));
+		}
+		if let Err(error) = self.name_normalization() {
+			problems.push(("normalize_names".to_string(), error.to_string()));
+		}
+		if let Err(error) = self.sort_order() {
+			problems.push(("sort".to_string(), error.to_string()));
+		}
+		if let Some(max_age) = &self.max_age {
+			if let Err(error) = util::parse_duration(max_age) {
+				problems.push(("max_age".to_string(), error.to_string()));
+			}
+		}
+		for key in self.reset.iter().flatten() {
+			if !RESETTABLE_KEYS.contains(&key.as_str()) {
+				problems.push(("reset".to_string(), format!("Unknown key in reset. [{}] ({})", key, RESETTABLE_KEYS.join(", "))));
+			}
+		}
+		for e in self.exclude_markers.iter().flatten() {
+			problems.extend(validate_dir_name(e).map(|message| ("exclude_markers".to_string(), message)));
+		}
+		if let Err(error) = manifest::Manifest::from_settings(self) {
+			problems.push(("manifest".to_string(), error.to_string()));
+		}
+		for (key, value) in [("min_size", &self.min_size), ("max_size", &self.max_size)] {
+			if let Some(Err(error)) = value.as_deref().map(util::parse_size) {
+				problems.push((key.to_string(), error.to_string()));
+			}
+		}
+		for (key, value) in [("newer_than", &self.newer_than), ("older_than", &self.older_than)] {
+			if let Some(Err(error)) = value.as_deref().map(util::parse_time) {
+				problems.push((key.to_string(), error.to_string()));
+			}
+		}
+		for name in self.presets.iter().flatten() {
+			if find_preset(name).is_none() {
+				problems.push(("presets".to_string(), format!("Unknown preset [{}]. ({})", name, preset_names())));
+			}
+		}
+
+		for (name, job) in self.jobs.iter().flatten() {
+			let table = format!("jobs.{}", name);
+			if job.sources.is_empty() {
+				problems.push((table.clone(), format!("No sources in job [{}].", name)));
+			}
+			if let Err(error) = crate::naming::NameTemplate::parse(&job.destination) {
+				problems.push((format!("{}.destination", table), error.to_string()));
+			}
+			if job.compression.is_some() {
+				let settings = Settings {
+					compression: job.compression.clone(),
+					..Default::default()
+				};
+				if let Err(error) = settings.compression_method() {
+					problems.push((format!("{}.compression", table), error.to_string()));
+				}
+			}
+			if job.compression.as_deref() == Some("stored") && job.compression_level.is_some() {
+				problems.push((
+					format!("{}.compression_level", table),
+					format!("compression_level cannot be used with stored compression in job [{}].", name),
+				));
+			}
+			for e in job.exclude_dirs.iter().flatten() {
+				problems.extend(validate_dir_name(e).map(|message| (format!("{}.exclude_dirs", table), message)));
+			}
+			for e in job.exclude_files.iter().flatten() {
+				problems.extend(validate_pattern(e).map(|message| (format!("{}.exclude_files", table), message)));
+			}
+			for e in job.exclude_markers.iter().flatten() {
+				problems.extend(validate_dir_name(e).map(|message| (format!("{}.exclude_markers", table), message)));
+			}
+			for (key, value) in [("min_size", &job.min_size), ("max_size", &job.max_size)] {
+				if let Some(Err(error)) = value.as_deref().map(util::parse_size) {
+					problems.push((format!("{}.{}", table, key), error.to_string()));
+				}
+			}
+			for (key, value) in [("newer_than", &job.newer_than), ("older_than", &job.older_than)] {
+				if let Some(Err(error)) = value.as_deref().map(util::parse_time) {
+					problems.push((format!("{}.{}", table, key), error.to_string()));
+				}
+			}
+			for name in job.presets.iter().flatten() {
+				if find_preset(name).is_none() {
+					problems.push((format!("{}.presets", table), format!("Unknown preset [{}]. ({})", name, preset_names())));
+				}
+			}
+		}
+
+		return problems;
+	}
+
+	/// Compression method of files.
+	pub fn compression_method(&self) -> Result<zip::CompressionMethod, Box<dyn std::error::Error>> {
+		let name = self.compression.as_deref().unwrap_or("deflated");
+		let method = match name {
+			"deflated" => zip::CompressionMethod::Deflated,
+			"stored" => zip::CompressionMethod::Stored,
+			"bzip2" => zip::CompressionMethod::Bzip2,
+			"zstd" => zip::CompressionMethod::Zstd,
+			_ => return Err(format!("Unknown compression method [{}]. (deflated, stored, bzip2, zstd)", name).into()),
+		};
+		return Ok(method);
+	}
+
+	/// Order of the entries in a directory. `None` keeps the order of the file system.
+	pub fn sort_order(&self) -> Result<Option<SortOrder>, Box<dyn std::error::Error>> {
+		let name = match self.sort.as_deref() {
+			// Reproducible archives are sorted by name.
+			None if self.reproducible == Some(true) => "lexical",
+			None => return Ok(None),
+			Some(name) => name,
+		};
+		let order = match name {
+			"lexical" => SortOrder::Lexical,
+			"dirs-first" => SortOrder::DirsFirst,
+			"size" => SortOrder::Size,
+			"extension" => SortOrder::Extension,
+			_ => return Err(format!("Unknown sort order [{}]. (lexical, dirs-first, size, extension)", name).into()),
+		};
+		return Ok(Some(order));
+	}
+
+	/// Handling of timestamps out of the DOS date range. (default: `clamp`)
+	pub fn time_policy(&self) -> Result<TimePolicy, Box<dyn std::error::Error>> {
+		let name = self.out_of_range_time.as_deref().unwrap_or("clamp");
+		let policy = match name {
+			"clamp" => TimePolicy::Clamp,
+			"now" => TimePolicy::Now,
+			"error" => TimePolicy::Error,
+			_ => return Err(format!("Unknown policy for timestamps out of range [{}]. (clamp, now, error)", name).into()),
+		};
+		return Ok(policy);
+	}
+
+	/// Handling of file names which are not valid UTF-8. (default: `escape`)
+	pub fn name_policy(&self) -> Result<NamePolicy, Box<dyn std::error::Error>> {
+		let name = self.non_utf8_names.as_deref().unwrap_or("escape");
+		let policy = match name {
+			"skip" => NamePolicy::Skip,
+			"escape" => NamePolicy::Escape,
+			"raw" => NamePolicy::Raw,
+			_ => return Err(format!("Unknown policy for non-UTF-8 names [{}]. (skip, escape, raw)", name).into()),
+		};
+		return Ok(policy);
+	}
+
+	/// Unicode normalization of the names in the archive. (default: `none`)
+	pub fn name_normalization(&self) -> Result<NameNormalization, Box<dyn std::error::Error>> {
+		let name = self.normalize_names.as_deref().unwrap_or("none");
+		let normalization = match name {
+			"none" => NameNormalization::None,
+			"nfc" => NameNormalization::Nfc,
+			"nfd" => NameNormalization::Nfd,
+			_ => return Err(format!("Unknown normalization [{}]. (nfc, nfd, none)", name).into()),
+		};
+		return Ok(normalization);
+	}
+
+	/// Encoding of the names in the archive. (default: `utf8`)
+	pub fn name_encoding(&self) -> Result<NameEncoding, Box<dyn std::error::Error>> {
+		return NameEncoding::parse(self.name_encoding.as_deref().unwrap_or("utf8"));
+	}
+
+	/// Names of the jobs.
+	pub fn job_names(&self) -> Vec<String> {
+		return match &self.jobs {
+			None => Vec::new(),
+			Some(jobs) => jobs.keys().cloned().collect(),
+		};
+	}
+
+	/// Retrieve the job and the settings applied to it.
+	///
+	/// # Arguments
+	/// * `name` Name of the job.
+	///
+	/// # Returns
+	/// The job and the global settings overridden by the job.
+	pub fn job(&self, name: &str) -> Result<(JobSettings, Settings), Box<dyn std::error::Error>> {
+		let job = self.jobs.as_ref().and_then(|jobs| jobs.get(name));
+		if job.is_none() {
+			return Err(format!("Job not found. [{}]", name).into());
+		}
+		let job = job.unwrap().clone();
+		if job.sources.is_empty() {
+			return Err(format!("No sources in job [{}].", name).into());
+		}
+
+		if job.compression.as_deref() == Some("stored") && job.compression_level.is_some() {
+			return Err(format!("compression_level cannot be used with stored compression in job [{}].", name).into());
+		}
+
+		let mut settings = self.clone();
+		settings.jobs = None;
+		if job.compression.is_some() {
+			settings.compression = job.compression.clone();
+		}
+		if job.compression_level.is_some() {
+			settings.compression_level = job.compression_level;
+		}
+		if job.incremental.is_some() {
+			settings.incremental = job.incremental.clone();
+		}
+		if let Some(dirs) = &job.exclude_dirs {
+			settings.exclude_dirs.get_or_insert_with(std::collections::HashSet::new).extend(dirs.iter().cloned());
+		}
+		if let Some(files) = &job.exclude_files {
+			settings
+				.exclude_files
+				.get_or_insert_with(std::collections::HashSet::new)
+				.extend(files.iter().cloned());
+		}
+
+		// Filters
+		merge_set(&mut settings.presets, job.presets.clone());
+		merge_set(&mut settings.exclude_markers, job.exclude_markers.clone());
+		override_with(&mut settings.exclude_caches, job.exclude_caches);
+		override_with(&mut settings.keep_tag_files, job.keep_tag_files);
+		override_with(&mut settings.min_size, job.min_size.clone());
+		override_with(&mut settings.max_size, job.max_size.clone());
+		override_with(&mut settings.newer_than, job.newer_than.clone());
+		override_with(&mut settings.older_than, job.older_than.clone());
+		override_with(&mut settings.max_depth, job.max_depth);
+		settings.expand_presets()?;
+
+		return Ok((job, settings));
+	}
+
+	/// Resolve filters evaluated on metadata of entries.
+	pub fn entry_filter(&self) -> Result<EntryFilter, Box<dyn std::error::Error>> {
+		let mut filter = EntryFilter::default();
+
+		if let Some(value) = &self.min_size {
+			filter.min_size = Some(util::parse_size(value)?);
+			filter.descriptions.push(format!("min_size = {}", value));
+		}
+		if let Some(value) = &self.max_size {
+			filter.max_size = Some(util::parse_size(value)?);
+			filter.descriptions.push(format!("max_size = {}", value));
+		}
+		if let Some(value) = &self.newer_than {
+			let time = util::parse_time(value)?;
+			filter.newer_than = Some(time);
+			filter.descriptions.push(format!(
+				"newer_than = {} ({})",
+				value,
+				chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M:%S")
+			));
+		}
+		if let Some(value) = &self.older_than {
+			let time = util::parse_time(value)?;
+			filter.older_than = Some(time);
+			filter.descriptions.push(format!(
+				"older_than = {} ({})",
+				value,
+				chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M:%S")
+			));
+		}
+		if let Some(value) = self.max_depth {
+			filter.max_depth = Some(value);
+			filter.descriptions.push(format!("max_depth = {}", value));
+		}
+
+		return Ok(filter);
+	}
+
+	/// Find a tag file which marks the directory to exclude. ("CACHEDIR.TAG" or a marker file)
+	///
+	/// # Arguments
+	/// * `dir` Path to the directory.
+	///
+	/// # Returns
+	/// Name of the tag file, or `None` if the directory is not tagged.
+	pub fn find_exclusion_tag(&self, dir: &std::path::Path) -> Option<String> {
+		if self.exclude_caches == Some(true) && is_cachedir_tag(&dir.join(CACHEDIR_TAG)) {
+			return Some(CACHEDIR_TAG.to_string());
+		}
+		for name in self.exclude_markers.iter().flatten() {
+			if dir.join(name).exists() {
+				return Some(name.to_string());
+			}
+		}
+		return None;
+	}
+
 	/// 指定された名前が処理対象か調べます。
 	///
 	/// # Arguments
@@ -175,7 +1428,7 @@ impl Settings {
 
 		let names = self.exclude_files.as_ref().unwrap();
 		for e in names {
-			let wildcard = make_name_filter(&e);
+			let wildcard = make_name_filter(e);
 			let regex = regex::Regex::new(&wildcard)?;
 			let matched = regex.is_match(name);
 			if matched {
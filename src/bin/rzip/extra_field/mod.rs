@@ -0,0 +1,260 @@
+//!
+//! Extra fields of zip entries.
+//!
+//! * `0x5455` Extended timestamp. (UTC, seconds since the UNIX epoch)
+//! * `0x7875` Unix owner. (uid, gid)
+//! * `0x7075` Unicode path. (UTF-8 name of an entry whose name is in a legacy encoding)
+//! * `0x7861` Extended attributes. (rzip specific)
+//! * `0x6c68` Hard link. (rzip specific)
+//!
+
+/// Header ID of the extended timestamp.
+pub const EXTENDED_TIMESTAMP: u16 = 0x5455;
+
+/// Header ID of the Unix owner.
+pub const UNIX_OWNER: u16 = 0x7875;
+
+/// Header ID of the Unicode path.
+pub const UNICODE_PATH: u16 = 0x7075;
+
+/// Header ID of the extended attributes.
+pub const XATTRS: u16 = 0x7861;
+
+/// Header ID of the hard link.
+pub const HARD_LINK: u16 = 0x6c68;
+
+/// Maximum size of the extra data in a header.
+pub const MAX_EXTRA_SIZE: usize = 0xFFFF;
+
+///
+/// Extended timestamp. (`0x5455`)
+///
+#[derive(std::fmt::Debug, std::default::Default, std::clone::Clone, std::cmp::PartialEq)]
+pub struct ExtendedTimestamp {
+	/// Last modified time.
+	pub mtime: Option<i32>,
+
+	/// Last accessed time.
+	pub atime: Option<i32>,
+
+	/// Creation (inode change) time.
+	pub ctime: Option<i32>,
+}
+
+impl ExtendedTimestamp {
+	/// Encode as the local header field. Every timestamp is stored.
+	pub fn to_local(&self) -> Vec<u8> {
+		let mut data: Vec<u8> = vec![self.flags()];
+		for time in [self.mtime, self.atime, self.ctime].iter().flatten() {
+			data.extend_from_slice(&time.to_le_bytes());
+		}
+		return encode(EXTENDED_TIMESTAMP, &data);
+	}
+
+	/// Encode as the central header field. Only the last modified time is stored.
+	pub fn to_central(&self) -> Vec<u8> {
+		let mut data: Vec<u8> = vec![self.flags()];
+		if let Some(mtime) = self.mtime {
+			data.extend_from_slice(&mtime.to_le_bytes());
+		}
+		return encode(EXTENDED_TIMESTAMP, &data);
+	}
+
+	/// Decode the local or central header field.
+	pub fn parse(data: &[u8]) -> Option<ExtendedTimestamp> {
+		let flags = *data.first()?;
+		let mut values = data[1..].chunks_exact(4).map(|e| i32::from_le_bytes([e[0], e[1], e[2], e[3]]));
+
+		let mut instance = ExtendedTimestamp::default();
+		if flags & 0x01 != 0 {
+			instance.mtime = values.next();
+		}
+		if flags & 0x02 != 0 {
+			instance.atime = values.next();
+		}
+		if flags & 0x04 != 0 {
+			instance.ctime = values.next();
+		}
+		return Some(instance);
+	}
+
+	/// Flags of the stored timestamps.
+	fn flags(&self) -> u8 {
+		let mut flags = 0u8;
+		if self.mtime.is_some() {
+			flags |= 0x01;
+		}
+		if self.atime.is_some() {
+			flags |= 0x02;
+		}
+		if self.ctime.is_some() {
+			flags |= 0x04;
+		}
+		return flags;
+	}
+}
+
+/// Encode the Unix owner field. (`0x7875`, version 1 with 4 bytes uid and gid)
+pub fn unix_owner(uid: u32, gid: u32) -> Vec<u8> {
+	let mut data: Vec<u8> = vec![1, 4];
+	data.extend_from_slice(&uid.to_le_bytes());
+	data.push(4);
+	data.extend_from_slice(&gid.to_le_bytes());
+	return encode(UNIX_OWNER, &data);
+}
+
+/// Encode the extended attributes field. (`0x7861`, version 1)
+///
+/// Each attribute is the length of the name (1 byte), the name, the length of the value (2 bytes) and the value.
+///
+/// # Arguments
+/// * `attributes` Names and values of the attributes. Names are shorter than 256 bytes.
+pub fn xattrs(attributes: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+	let mut data: Vec<u8> = vec![1];
+	for (name, value) in attributes {
+		data.push(name.len() as u8);
+		data.extend_from_slice(name);
+		data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+		data.extend_from_slice(value);
+	}
+	return encode(XATTRS, &data);
+}
+
+/// Size of an attribute in the extended attributes field.
+pub fn xattr_size(name: &[u8], value: &[u8]) -> usize {
+	return 1 + name.len() + 2 + value.len();
+}
+
+/// Decode the extended attributes field. (names and values)
+pub fn parse_xattrs(data: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+	if data.first() != Some(&1) {
+		return None;
+	}
+	let mut attributes: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+	let mut position = 1;
+	while position < data.len() {
+		let name_size = *data.get(position)? as usize;
+		let name = data.get(position + 1..position + 1 + name_size)?;
+		position += 1 + name_size;
+		let value_size = read_uint(data.get(position..position + 2)?)? as usize;
+		let value = data.get(position + 2..position + 2 + value_size)?;
+		position += 2 + value_size;
+		attributes.push((name.to_vec(), value.to_vec()));
+	}
+	return Some(attributes);
+}
+
+/// Encode the hard link field. (`0x6c68`, version 1 with the name of the target entry)
+///
+/// # Arguments
+/// * `target` Name of the target entry as stored in its header.
+pub fn hard_link(target: &[u8]) -> Vec<u8> {
+	let mut data: Vec<u8> = vec![1];
+	data.extend_from_slice(target);
+	return encode(HARD_LINK, &data);
+}
+
+/// Decode the hard link field. (name of the target entry)
+pub fn parse_hard_link(data: &[u8]) -> Option<&[u8]> {
+	if data.first() != Some(&1) {
+		return None;
+	}
+	return Some(&data[1..]);
+}
+
+/// Decode the Unix owner field. (uid, gid)
+pub fn parse_unix_owner(data: &[u8]) -> Option<(u32, u32)> {
+	if data.first() != Some(&1) {
+		return None;
+	}
+	let uid_size = *data.get(1)? as usize;
+	let uid = read_uint(data.get(2..2 + uid_size)?)?;
+	let gid_size = *data.get(2 + uid_size)? as usize;
+	let gid = read_uint(data.get(3 + uid_size..3 + uid_size + gid_size)?)?;
+	return Some((uid, gid));
+}
+
+/// Find a field in the extra data.
+///
+/// # Arguments
+/// * `extra` Extra data of the local or central header.
+/// * `id` Header ID.
+///
+/// # Returns
+/// Data of the field without the header.
+pub fn find(extra: &[u8], id: u16) -> Option<&[u8]> {
+	let mut position = 0;
+	while position + 4 <= extra.len() {
+		let header = u16::from_le_bytes([extra[position], extra[position + 1]]);
+		let size = u16::from_le_bytes([extra[position + 2], extra[position + 3]]) as usize;
+		let data = extra.get(position + 4..position + 4 + size)?;
+		if header == id {
+			return Some(data);
+		}
+		position += 4 + size;
+	}
+	return None;
+}
+
+/// Read the extra data in the local header of an entry.
+///
+/// # Arguments
+/// * `file` Archive.
+/// * `header_start` Offset of the local header.
+pub fn read_local(file: &mut std::fs::File, header_start: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+	use std::io::{Read, Seek};
+
+	let mut header = [0u8; 30];
+	file.seek(std::io::SeekFrom::Start(header_start))?;
+	file.read_exact(&mut header)?;
+	if header[0..4] != [0x50, 0x4b, 0x03, 0x04] {
+		return Err("Invalid local file header.".into());
+	}
+	let name_length = u16::from_le_bytes([header[26], header[27]]) as i64;
+	let extra_length = u16::from_le_bytes([header[28], header[29]]) as usize;
+
+	let mut extra = vec![0u8; extra_length];
+	file.seek(std::io::SeekFrom::Current(name_length))?;
+	file.read_exact(&mut extra)?;
+	return Ok(extra);
+}
+
+/// Encode a field with its header.
+fn encode(id: u16, data: &[u8]) -> Vec<u8> {
+	let mut result: Vec<u8> = Vec::with_capacity(4 + data.len());
+	result.extend_from_slice(&id.to_le_bytes());
+	result.extend_from_slice(&(data.len() as u16).to_le_bytes());
+	result.extend_from_slice(data);
+	return result;
+}
+
+/// Read little endian unsigned integer up to 4 bytes.
+fn read_uint(bytes: &[u8]) -> Option<u32> {
+	if 4 < bytes.len() {
+		return None;
+	}
+	let mut value = 0u32;
+	for (i, byte) in bytes.iter().enumerate() {
+		value |= (*byte as u32) << (8 * i);
+	}
+	return Some(value);
+}
+
+/// Seconds since the UNIX epoch. `None` if it does not fit in the field.
+pub fn to_unix_time(time: std::time::SystemTime) -> Option<i32> {
+	use std::convert::TryFrom;
+
+	let seconds = match time.duration_since(std::time::UNIX_EPOCH) {
+		Ok(duration) => duration.as_secs() as i64,
+		Err(e) => -(e.duration().as_secs() as i64),
+	};
+	return i32::try_from(seconds).ok();
+}
+
+/// Convert seconds since the UNIX epoch to [std::time::SystemTime].
+pub fn from_unix_time(seconds: i32) -> std::time::SystemTime {
+	if seconds < 0 {
+		return std::time::UNIX_EPOCH - std::time::Duration::from_secs(-(seconds as i64) as u64);
+	}
+	return std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds as u64);
+}
@@ -0,0 +1,329 @@
+//!
+//! Zip extractor module
+//!
+
+use crate::configuration;
+use crate::extra_field;
+use crate::names;
+
+///
+/// Attributes of an entry restored after extraction.
+///
+struct EntryAttributes {
+	/// Last modified time.
+	mtime: std::time::SystemTime,
+
+	/// Last accessed time.
+	atime: Option<std::time::SystemTime>,
+
+	/// Unix permissions.
+	mode: Option<u32>,
+
+	/// Unix owner. (uid, gid)
+	owner: Option<(u32, u32)>,
+
+	/// Extended attributes. (names and values)
+	xattrs: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl EntryAttributes {
+	/// Read attributes of an entry.
+	///
+	/// # Arguments
+	/// * `entry` Entry of the archive.
+	/// * `local_extra` Extra data of the local header.
+	fn read(entry: &zip::read::ZipFile, local_extra: &[u8]) -> EntryAttributes {
+		// Extended timestamp in the local header has all of the timestamps. The central header has only mtime.
+		let timestamp = extra_field::find(local_extra, extra_field::EXTENDED_TIMESTAMP)
+			.or_else(|| extra_field::find(entry.extra_data(), extra_field::EXTENDED_TIMESTAMP))
+			.and_then(extra_field::ExtendedTimestamp::parse)
+			.unwrap_or_default();
+
+		// Fall back on DOS date time. (local time)
+		let mtime = match timestamp.mtime {
+			Some(mtime) => extra_field::from_unix_time(mtime),
+			None => from_ziptime(entry.last_modified()),
+		};
+
+		let owner = extra_field::find(local_extra, extra_field::UNIX_OWNER)
+			.or_else(|| extra_field::find(entry.extra_data(), extra_field::UNIX_OWNER))
+			.and_then(extra_field::parse_unix_owner);
+
+		let xattrs = extra_field::find(local_extra, extra_field::XATTRS)
+			.and_then(extra_field::parse_xattrs)
+			.unwrap_or_default();
+
+		return EntryAttributes {
+			mtime,
+			atime: timestamp.atime.map(extra_field::from_unix_time),
+			mode: entry.unix_mode(),
+			owner,
+			xattrs,
+		};
+	}
+}
+
+///
+/// Zip extractor class
+///
+pub struct ZipExtractor {
+	/// Restore the owner of the entries.
+	same_owner: bool,
+
+	/// Encoding of the names without the language encoding flag. `None` detects it.
+	name_encoding: Option<configuration::NameEncoding>,
+
+	/// Restore extended attributes.
+	xattrs: bool,
+
+	/// Restore setuid, setgid and sticky bits.
+	special_bits: bool,
+}
+
+impl ZipExtractor {
+	/// Returns a new instance of [ZipExtractor].
+	///
+	/// # Arguments
+	/// * `same_owner` Restore the owner of the entries. (usually requires root)
+	/// * `name_encoding` Encoding of the names without the language encoding flag. `None` detects it.
+	/// * `xattrs` Restore extended attributes.
+	/// * `special_bits` Restore setuid, setgid and sticky bits. Masked by default as the archive is not trusted.
+	pub fn new(same_owner: bool, name_encoding: Option<configuration::NameEncoding>, xattrs: bool, special_bits: bool) -> ZipExtractor {
+		return ZipExtractor {
+			same_owner,
+			name_encoding,
+			xattrs,
+			special_bits,
+		};
+	}
+
+	/// Encoding of the names without the language encoding flag.
+	///
+	/// # Returns
+	/// `None` if no encoding decodes all of them.
+	fn detect_encoding(
+		&self,
+		archive: &mut zip::ZipArchive<std::fs::File>,
+		raw: &mut std::fs::File,
+	) -> Result<Option<configuration::NameEncoding>, Box<dyn std::error::Error>> {
+		if self.name_encoding.is_some() {
+			return Ok(self.name_encoding);
+		}
+
+		let mut legacy: Vec<Vec<u8>> = Vec::new();
+		for index in 0..archive.len() {
+			let entry = archive.by_index_raw(index)?;
+			let flags = names::read_flags(raw, entry.header_start())?;
+			if names::decode_name(entry.name_raw(), flags, entry.extra_data(), None).is_none() {
+				legacy.push(entry.name_raw().to_vec());
+			}
+		}
+		if legacy.is_empty() {
+			return Ok(None);
+		}
+
+		let encoding = names::detect_encoding(&legacy.iter().map(|e| e.as_slice()).collect::<Vec<&[u8]>>());
+		match encoding {
+			None => println!("[WARN] Encoding of the names is unknown. Use --name-encoding."),
+			Some(encoding) => println!("[INFO] Encoding of the names is detected as {:?}.", encoding),
+		}
+		return Ok(encoding);
+	}
+
+	/// Extract all entries of the archive.
+	///
+	/// # Arguments
+	/// * `path_to_archive` Path to the archive.
+	/// * `destination` Directory to extract into.
+	pub fn extract(&self, path_to_archive: &str, destination: &str) -> Result<(), Box<dyn std::error::Error>> {
+		let file = std::fs::File::open(path_to_archive)?;
+		let mut archive = zip::ZipArchive::new(file)?;
+
+		// Another handle to read the local headers.
+		let mut raw = std::fs::File::open(path_to_archive)?;
+
+		let encoding = self.detect_encoding(&mut archive, &mut raw)?;
+
+		// Extracted files by the names in the headers. For hard links.
+		let mut extracted: std::collections::HashMap<Vec<u8>, std::path::PathBuf> = std::collections::HashMap::new();
+
+		// Attributes of directories are restored after their contents.
+		let mut directories: Vec<(std::path::PathBuf, EntryAttributes)> = Vec::new();
+
+		for index in 0..archive.len() {
+			let mut entry = archive.by_index(index)?;
+
+			// Reject absolute paths and "..".
+			let flags = names::read_flags(&mut raw, entry.header_start())?;
+			let decoded = names::decode_name(entry.name_raw(), flags, entry.extra_data(), encoding);
+			let name = decoded.clone().unwrap_or_else(|| names::escape_bytes(entry.name_raw()));
+			let relative = match enclosed_name(decoded, entry.name_raw()) {
+				None => {
+					eprintln!("[ERROR] SKIP {} (unsafe path)", name);
+					continue;
+				}
+				Some(relative) => relative,
+			};
+			let target = std::path::Path::new(destination).join(&relative);
+
+			let local_extra = extra_field::read_local(&mut raw, entry.header_start())?;
+			let attributes = EntryAttributes::read(&entry, &local_extra);
+
+			println!("  extracting: {}", name);
+
+			if entry.is_dir() {
+				std::fs::create_dir_all(&target)?;
+				directories.push((target, attributes));
+				continue;
+			}
+
+			if let Some(parent) = target.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+
+			// Hard link to a file extracted already. Attributes are shared with it.
+			let link = extra_field::find(&local_extra, extra_field::HARD_LINK)
+				.or_else(|| extra_field::find(entry.extra_data(), extra_field::HARD_LINK))
+				.and_then(extra_field::parse_hard_link);
+			if let Some(link) = link {
+				match extracted.get(link) {
+					None => eprintln!("[ERROR] SKIP {} (target of hard link is not extracted)", name),
+					Some(source) => {
+						if target.exists() {
+							std::fs::remove_file(&target)?;
+						}
+						std::fs::hard_link(source, &target)?;
+					}
+				}
+				continue;
+			}
+			extracted.insert(entry.name_raw().to_vec(), target.clone());
+
+			let mut stream = std::fs::File::create(&target)?;
+			std::io::copy(&mut entry, &mut stream)?;
+			drop(stream);
+
+			self.restore(&target, &attributes)?;
+		}
+
+		// Deepest first.
+		for (target, attributes) in directories.iter().rev() {
+			self.restore(target, attributes)?;
+		}
+
+		return Ok(());
+	}
+
+	/// Restore attributes of an extracted entry.
+	fn restore(&self, target: &std::path::Path, attributes: &EntryAttributes) -> Result<(), Box<dyn std::error::Error>> {
+		// Owner first. Changing owner may clear setuid bits.
+		#[cfg(unix)]
+		if self.same_owner {
+			if let Some((uid, gid)) = attributes.owner {
+				std::os::unix::fs::chown(target, Some(uid), Some(gid))?;
+			}
+		}
+
+		// Extended attributes after the owner. Changing owner clears file capabilities.
+		#[cfg(unix)]
+		if self.xattrs {
+			use std::os::unix::ffi::OsStrExt;
+			for (name, value) in &attributes.xattrs {
+				let name = std::ffi::OsStr::from_bytes(name);
+				if let Err(error) = xattr::set(target, name, value) {
+					println!(
+						"[WARN] Extended attribute {} of {} cannot be restored. ({})",
+						name.to_string_lossy(),
+						target.display(),
+						error
+					);
+				}
+			}
+		}
+
+		// Timestamps
+		let mut times = std::fs::FileTimes::new().set_modified(attributes.mtime);
+		if let Some(atime) = attributes.atime {
+			times = times.set_accessed(atime);
+		}
+		open_to_set_times(target)?.set_times(times)?;
+
+		// Permissions
+		#[cfg(unix)]
+		if let Some(mode) = attributes.mode {
+			use std::os::unix::fs::PermissionsExt;
+			let mask = if self.special_bits { 0o7777 } else { 0o777 };
+			std::fs::set_permissions(target, std::fs::Permissions::from_mode(mode & mask))?;
+		}
+
+		return Ok(());
+	}
+}
+
+/// Open a file or a directory with the access to change its timestamps.
+///
+/// # Arguments
+/// * `path` Path to the extracted entry.
+fn open_to_set_times(path: &std::path::Path) -> std::io::Result<std::fs::File> {
+	// Windows needs FILE_WRITE_ATTRIBUTES, and FILE_FLAG_BACKUP_SEMANTICS to open directories.
+	#[cfg(windows)]
+	{
+		use std::os::windows::fs::OpenOptionsExt;
+		const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+		const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+		return std::fs::OpenOptions::new()
+			.access_mode(FILE_WRITE_ATTRIBUTES)
+			.custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+			.open(path);
+	}
+
+	#[cfg(not(windows))]
+	{
+		if path.is_dir() {
+			return std::fs::File::open(path);
+		}
+		return std::fs::OpenOptions::new().write(true).open(path);
+	}
+}
+
+/// Relative path of the entry. `None` if the path is absolute or contains "..".
+///
+/// # Arguments
+/// * `decoded` Decoded name. `None` restores the bytes as they are. (code page 437 except Unix)
+/// * `name_raw` Name in the header.
+fn enclosed_name(decoded: Option<String>, name_raw: &[u8]) -> Option<std::path::PathBuf> {
+	let path = match decoded {
+		Some(decoded) => std::path::PathBuf::from(decoded.replace('\\', "/")),
+		None => raw_path(name_raw),
+	};
+	if path.as_os_str().is_empty() || !path.components().all(|e| matches!(e, std::path::Component::Normal(_) | std::path::Component::CurDir)) {
+		return None;
+	}
+	return Some(path);
+}
+
+/// Path of the bytes as they are.
+#[cfg(unix)]
+fn raw_path(name_raw: &[u8]) -> std::path::PathBuf {
+	use std::os::unix::ffi::OsStrExt;
+	return std::path::PathBuf::from(std::ffi::OsStr::from_bytes(name_raw));
+}
+
+/// Path of the bytes decoded as code page 437.
+#[cfg(not(unix))]
+fn raw_path(name_raw: &[u8]) -> std::path::PathBuf {
+	return std::path::PathBuf::from(names::decode(name_raw, configuration::NameEncoding::Cp437).unwrap());
+}
+
+/// Convert DOS date time. (local time)
+fn from_ziptime(time: zip::DateTime) -> std::time::SystemTime {
+	use chrono::TimeZone;
+
+	let date = chrono::NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32);
+	let date = date.and_then(|e| e.and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32));
+	let date = date.and_then(|e| chrono::Local.from_local_datetime(&e).earliest());
+	return match date {
+		None => std::time::UNIX_EPOCH,
+		Some(date) => std::time::SystemTime::from(date),
+	};
+}
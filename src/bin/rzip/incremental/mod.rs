@@ -0,0 +1,260 @@
+//!
+//! Incremental archiving against the state of the previous run.
+//!
+
+use crate::configuration;
+use crate::util;
+
+/// Name of the entry which holds the state in the archive.
+pub const STATE_ENTRY_NAME: &str = ".rzip/state.json";
+
+/// Name of the entry which lists files deleted since the previous run.
+pub const DELETIONS_ENTRY_NAME: &str = ".rzip/deleted.txt";
+
+///
+/// State of an archived file.
+///
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, std::fmt::Debug, std::clone::Clone, std::cmp::PartialEq)]
+pub struct FileState {
+	/// Size in bytes.
+	pub size: u64,
+
+	/// Last modified time. (seconds since the UNIX epoch)
+	pub mtime: i64,
+
+	/// Last modified time. (nanoseconds part)
+	pub mtime_nanos: u32,
+
+	/// SHA-256 of the content. (hex)
+	pub sha256: String,
+
+	/// Printable name with raw bytes as `%XX`. Only for names which are not valid UTF-8.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub escaped: Option<String>,
+}
+
+impl FileState {
+	/// Create a new instance from metadata and hash of the content.
+	pub fn new(meta: &std::fs::Metadata, sha256: &str) -> Result<FileState, Box<dyn std::error::Error>> {
+		let (mtime, mtime_nanos) = unix_time_of(meta.modified()?);
+		let instance = FileState {
+			size: meta.len(),
+			mtime,
+			mtime_nanos,
+			sha256: sha256.to_string(),
+			escaped: None,
+		};
+		return Ok(instance);
+	}
+}
+
+///
+/// State of the whole run.
+///
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, std::fmt::Debug, std::default::Default)]
+pub struct State {
+	/// Timestamp of the run. `SOURCE_DATE_EPOCH` if set, and omitted in reproducible mode without it.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub created: Option<String>,
+
+	/// Archived files. (internal path, state)
+	pub files: std::collections::BTreeMap<String, FileState>,
+}
+
+impl State {
+	/// Load state from a state file or `.rzip/state.json` in a previous archive.
+	///
+	/// # Arguments
+	/// * `path` Path to a state file or a previous archive. Empty state if it does not exist.
+	pub fn load(path: &str) -> Result<State, Box<dyn std::error::Error>> {
+		if !std::path::Path::new(path).is_file() {
+			println!("[INFO] No previous state [{}]. All files are archived.", path);
+			return Ok(State::default());
+		}
+
+		let content = if is_archive_path(path) {
+			use std::io::Read;
+
+			let file = std::fs::File::open(path)?;
+			let mut archive = zip::ZipArchive::new(file)?;
+			let entry = archive.by_name(STATE_ENTRY_NAME);
+			if entry.is_err() {
+				return Err(format!("No incremental state in [{}]. ({} not found)", path, STATE_ENTRY_NAME).into());
+			}
+			let mut content = String::new();
+			entry.unwrap().read_to_string(&mut content)?;
+			content
+		} else {
+			util::read_text_file_all(path)?
+		};
+
+		let result = serde_json::from_str::<State>(&content);
+		if result.is_err() {
+			return Err(format!("Invalid incremental state in [{}]. reason: {}", path, result.err().unwrap()).into());
+		}
+		return Ok(result.unwrap());
+	}
+
+	/// Serialize as JSON.
+	pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+		return Ok(serde_json::to_string_pretty(self)?);
+	}
+}
+
+///
+/// Incremental archiving.
+///
+pub struct Incremental {
+	/// Path to a state file or a previous archive.
+	path: String,
+
+	/// State of the previous run.
+	previous: State,
+
+	/// State of this run.
+	current: State,
+
+	/// Write the list of deleted files into the archive.
+	record_deletions: bool,
+
+	/// Files excluded in this run. They are not deleted.
+	skipped_files: std::collections::HashSet<String>,
+
+	/// Directories excluded in this run, with a trailing separator. Files under them are not deleted.
+	skipped_dirs: Vec<String>,
+}
+
+impl Incremental {
+	/// Create from [configuration::Settings]. Returns `None` if incremental archiving is not configured.
+	pub fn from_settings(settings: &configuration::Settings) -> Result<Option<Incremental>, Box<dyn std::error::Error>> {
+		let path = match &settings.incremental {
+			None => return Ok(None),
+			Some(path) => path,
+		};
+
+		let created = match util::source_date_epoch()? {
+			Some(epoch) => Some(chrono::DateTime::<chrono::Utc>::from(epoch).to_rfc3339()),
+			None if settings.reproducible == Some(true) => None,
+			None => Some(chrono::Local::now().to_rfc3339()),
+		};
+
+		let instance = Incremental {
+			path: path.to_string(),
+			previous: State::load(path)?,
+			current: State {
+				created,
+				files: std::collections::BTreeMap::new(),
+			},
+			record_deletions: settings.record_deletions == Some(true),
+			skipped_files: std::collections::HashSet::new(),
+			skipped_dirs: Vec::new(),
+		};
+		return Ok(Some(instance));
+	}
+
+	/// Find the file in the previous state.
+	///
+	/// # Arguments
+	/// * `internal_path` Relative path from the root. "path/to/name"
+	/// * `path` Path to the file.
+	/// * `meta` Metadata of the file.
+	///
+	/// # Returns
+	/// State to record if the file is unchanged since the previous run, or `None` if it is new or changed.
+	pub fn find_unchanged(&self, internal_path: &str, path: &std::path::Path, meta: &std::fs::Metadata) -> Result<Option<FileState>, Box<dyn std::error::Error>> {
+		let previous = match self.previous.files.get(internal_path) {
+			None => return Ok(None),
+			Some(previous) => previous,
+		};
+		if previous.size != meta.len() {
+			return Ok(None);
+		}
+
+		let (mtime, mtime_nanos) = unix_time_of(meta.modified()?);
+		if previous.mtime == mtime && previous.mtime_nanos == mtime_nanos {
+			return Ok(Some(previous.clone()));
+		}
+
+		// Touched but the same size. Compare the content.
+		let sha256 = util::hash_file(path)?;
+		if previous.sha256 != sha256 {
+			return Ok(None);
+		}
+		return Ok(Some(FileState::new(meta, &sha256)?));
+	}
+
+	/// Record a file of this run.
+	///
+	/// # Arguments
+	/// * `internal_path` Relative path from the root. "path/to/name"
+	/// * `display` Printable form of `internal_path`. (raw bytes as `%XX`)
+	/// * `state` State of the file.
+	pub fn record(&mut self, internal_path: &str, display: &str, state: FileState) {
+		let mut state = state;
+		state.escaped = if display == internal_path { None } else { Some(display.to_string()) };
+		self.current.files.insert(internal_path.to_string(), state);
+	}
+
+	/// Record an entry excluded by filters in this run. It is not reported as deleted.
+	///
+	/// # Arguments
+	/// * `internal_path` Relative path from the root. "path/to/name" (empty for the root)
+	/// * `is_dir` The entry is a directory. Everything under it is excluded.
+	pub fn skip(&mut self, internal_path: &str, is_dir: bool) {
+		if !is_dir {
+			self.skipped_files.insert(internal_path.to_string());
+		} else if internal_path == "" {
+			self.skipped_dirs.push(String::new());
+		} else {
+			self.skipped_dirs.push(format!("{}/", internal_path));
+		}
+	}
+
+	/// Files in the previous state which are not found in this run. Printable names.
+	pub fn deletions(&self) -> Vec<String> {
+		let mut result: Vec<String> = Vec::new();
+		for (path, state) in &self.previous.files {
+			if self.current.files.contains_key(path) || self.skipped_files.contains(path) {
+				continue;
+			}
+			if self.skipped_dirs.iter().any(|dir| path.starts_with(dir.as_str())) {
+				continue;
+			}
+			result.push(state.escaped.clone().unwrap_or_else(|| path.to_string()));
+		}
+		return result;
+	}
+
+	/// Write the list of deleted files into the archive.
+	pub fn records_deletions(&self) -> bool {
+		return self.record_deletions;
+	}
+
+	/// State of this run.
+	pub fn current(&self) -> &State {
+		return &self.current;
+	}
+
+	/// Update the state file. Nothing to do if the previous state was read from an archive.
+	pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+		if is_archive_path(&self.path) {
+			return Ok(());
+		}
+		std::fs::write(&self.path, self.current.to_json()?)?;
+		println!("[INFO] incremental state saved. [{}]", &self.path);
+		return Ok(());
+	}
+}
+
+/// Returns `true` if the path refers to an archive instead of a state file.
+fn is_archive_path(path: &str) -> bool {
+	return path.to_lowercase().ends_with(".zip");
+}
+
+/// Split time into seconds and nanoseconds since the UNIX epoch.
+fn unix_time_of(time: std::time::SystemTime) -> (i64, u32) {
+	match time.duration_since(std::time::UNIX_EPOCH) {
+		Ok(duration) => return (duration.as_secs() as i64, duration.subsec_nanos()),
+		Err(e) => return (-(e.duration().as_secs() as i64), e.duration().subsec_nanos()),
+	}
+}
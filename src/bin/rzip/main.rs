@@ -2,11 +2,27 @@
 //! Entrypoint of application.
 //!
 
+#![allow(clippy::needless_return)]
+#![allow(clippy::comparison_to_empty)]
+
 mod application;
 mod archiver;
 mod configuration;
+mod extra_field;
+mod extractor;
+mod incremental;
+mod manifest;
+mod names;
+mod naming;
+mod portability;
+mod project;
+mod retention;
+mod signature;
 mod util;
 
+/// Origin of the settings given by commandline options.
+const COMMAND_LINE: &str = "(command line)";
+
 struct CommandlineOptions {
 	// Core options.
 	options: getopts::Options,
@@ -20,8 +36,113 @@ struct CommandlineOptions {
 	/// Option: --sleep
 	pub sleep: Option<f32>,
 
+	/// Option: --config
+	pub config: Option<String>,
+
+	/// Option: --dry-run
+	pub dry_run: bool,
+
+	/// Option: --preset
+	pub presets: Vec<String>,
+
+	/// Option: --exclude-caches
+	pub exclude_caches: bool,
+
+	/// Option: --exclude-marker
+	pub exclude_markers: Vec<String>,
+
+	/// Option: --keep-tag-files
+	pub keep_tag_files: bool,
+
+	/// Option: --incremental
+	pub incremental: Option<String>,
+
+	/// Option: --record-deletions
+	pub record_deletions: bool,
+
+	/// Option: --manifest
+	pub manifest: Option<String>,
+
+	/// Option: --checksum
+	pub checksum_file: bool,
+
+	/// Option: --sign
+	pub sign: Option<String>,
+
+	/// Option: --sort
+	pub sort: Option<String>,
+
+	/// Option: --out-of-range-time
+	pub out_of_range_time: Option<String>,
+
+	/// Option: --non-utf8-names
+	pub non_utf8_names: Option<String>,
+
+	/// Option: --name-encoding
+	pub name_encoding: Option<String>,
+
+	/// Option: --normalize-names
+	pub normalize_names: Option<String>,
+
+	/// Option: --portable
+	pub portable: bool,
+
+	/// Option: --xattrs
+	pub xattrs: bool,
+
+	/// Option: --hard-links
+	pub hard_links: bool,
+
+	/// Option: --one-file-system
+	pub one_file_system: bool,
+
+	/// Option: --reproducible
+	pub reproducible: bool,
+
+	/// Option: --same-owner
+	pub same_owner: bool,
+
+	/// Option: --keep-special-bits
+	pub keep_special_bits: bool,
+
+	/// Option: --min-size
+	pub min_size: Option<String>,
+
+	/// Option: --max-size
+	pub max_size: Option<String>,
+
+	/// Option: --newer-than
+	pub newer_than: Option<String>,
+
+	/// Option: --older-than
+	pub older_than: Option<String>,
+
+	/// Option: --max-depth
+	pub max_depth: Option<usize>,
+
+	/// Option: --all
+	pub all: bool,
+
+	/// Option: --keep-last
+	pub keep_last: Option<usize>,
+
+	/// Option: --keep-daily
+	pub keep_daily: Option<usize>,
+
+	/// Option: --keep-weekly
+	pub keep_weekly: Option<usize>,
+
+	/// Option: --keep-monthly
+	pub keep_monthly: Option<usize>,
+
+	/// Option: --max-age
+	pub max_age: Option<String>,
+
 	/// Others.
 	pub free: Vec<String>,
+
+	/// Path to the source. (2nd free argument) It may not be valid UTF-8.
+	pub source: Option<std::path::PathBuf>,
 }
 
 impl CommandlineOptions {
@@ -38,13 +159,313 @@ impl CommandlineOptions {
 			getopts::HasArg::Yes,
 			getopts::Occur::Optional,
 		);
+		options.opt(
+			"",
+			"config",
+			"Configuration file. (overrides the other ones)",
+			"FILE",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"dry-run",
+			"Show what would be done without writing anything.",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"preset",
+			"Enable an exclusion preset. (rust, node, python, dotnet, java, editor, os-junk)",
+			"NAME",
+			getopts::HasArg::Yes,
+			getopts::Occur::Multi,
+		);
+		options.opt(
+			"",
+			"exclude-caches",
+			"Exclude directories containing CACHEDIR.TAG.",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"exclude-marker",
+			"Exclude directories containing the file. (e.g. .nobackup)",
+			"NAME",
+			getopts::HasArg::Yes,
+			getopts::Occur::Multi,
+		);
+		options.opt(
+			"",
+			"keep-tag-files",
+			"Keep the tag file in the excluded directory.",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"incremental",
+			"Archive only files new or changed since the state in {file}. (state file or previous archive)",
+			"FILE",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"record-deletions",
+			"Write files deleted since the previous run into the archive. (.rzip/deleted.txt)",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"manifest",
+			"Write a manifest of the entries into the archive. (sha256 or json)",
+			"FORMAT",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"checksum",
+			"Write SHA-256 of the archive next to it. ({archive}.sha256)",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"sign",
+			"Sign the archive with the secret key in {file}. ({archive}.minisig)",
+			"FILE",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"sort",
+			"Order of the entries in a directory. (lexical, dirs-first, size, extension)",
+			"ORDER",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"out-of-range-time",
+			"Timestamps out of the DOS date range. (clamp, now, error)",
+			"POLICY",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"non-utf8-names",
+			"File names which are not valid UTF-8. (skip, escape, raw)",
+			"POLICY",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"name-encoding",
+			"Encoding of the names in the archive. (utf8, cp932, cp437) Detected when extracting if omitted.",
+			"ENCODING",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"normalize-names",
+			"Unicode normalization of the names. (nfc, nfd, none)",
+			"FORM",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"portable",
+			"Fail if names break on Windows or macOS.",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"xattrs",
+			"Store extended attributes of files, or restore them on extract. (Unix only)",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"hard-links",
+			"Store hard links once. Later links refer to the first entry.",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"one-file-system",
+			"Do not descend into directories on other file systems.",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"reproducible",
+			"Make byte-identical archives from identical inputs. (honours SOURCE_DATE_EPOCH)",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"same-owner",
+			"Restore the owner of the entries on extract. (usually requires root)",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"keep-special-bits",
+			"Restore setuid, setgid and sticky bits on extract.",
+			"",
+			getopts::HasArg::No,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"min-size",
+			"Exclude files smaller than {size}. (e.g. 1K)",
+			"SIZE",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"max-size",
+			"Exclude files larger than {size}. (e.g. 10M)",
+			"SIZE",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"newer-than",
+			"Exclude files modified before {time}. (date, duration before now or path to a file)",
+			"TIME",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"older-than",
+			"Exclude files modified after {time}. (date, duration before now or path to a file)",
+			"TIME",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"max-depth",
+			"Do not descend deeper than {n} below the source.",
+			"N",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt("", "all", "Run all jobs. (with 'run')", "", getopts::HasArg::No, getopts::Occur::Optional);
+		options.opt(
+			"",
+			"keep-last",
+			"Keep the last {n} archives of the same name template.",
+			"N",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"keep-daily",
+			"Keep the last archive of {n} days.",
+			"N",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"keep-weekly",
+			"Keep the last archive of {n} weeks.",
+			"N",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"keep-monthly",
+			"Keep the last archive of {n} months.",
+			"N",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
+		options.opt(
+			"",
+			"max-age",
+			"Remove archives older than {duration}. (e.g. 30d, 12h, 2w)",
+			"DURATION",
+			getopts::HasArg::Yes,
+			getopts::Occur::Optional,
+		);
 
 		let instance = CommandlineOptions {
-			options: options,
+			options,
 			help: false,
 			root: false,
 			sleep: None,
+			config: None,
+			dry_run: false,
+			presets: Vec::new(),
+			exclude_caches: false,
+			exclude_markers: Vec::new(),
+			keep_tag_files: false,
+			incremental: None,
+			record_deletions: false,
+			manifest: None,
+			checksum_file: false,
+			sign: None,
+			sort: None,
+			out_of_range_time: None,
+			non_utf8_names: None,
+			name_encoding: None,
+			normalize_names: None,
+			portable: false,
+			xattrs: false,
+			hard_links: false,
+			one_file_system: false,
+			reproducible: false,
+			same_owner: false,
+			keep_special_bits: false,
+			min_size: None,
+			max_size: None,
+			newer_than: None,
+			older_than: None,
+			max_depth: None,
+			all: false,
+			keep_last: None,
+			keep_daily: None,
+			keep_weekly: None,
+			keep_monthly: None,
+			max_age: None,
 			free: Vec::new(),
+			source: None,
 		};
 
 		return instance;
@@ -52,10 +473,15 @@ impl CommandlineOptions {
 
 	/// Parse commandline options.
 	pub fn parse(&mut self) -> std::result::Result<(), Box<dyn std::error::Error>> {
-		// Commandline options.
-		let args: Vec<String> = std::env::args().skip(1).collect();
+		// Commandline options. getopts accepts only UTF-8, so other arguments are replaced while parsing. (NUL never appears in arguments)
+		let args: Vec<std::ffi::OsString> = std::env::args_os().skip(1).collect();
+		let substitutes: Vec<String> = args
+			.iter()
+			.enumerate()
+			.map(|(index, arg)| arg.to_str().map(|e| e.to_string()).unwrap_or_else(|| format!("\0{}", index)))
+			.collect();
 
-		let matches = self.options.parse(args)?;
+		let matches = self.options.parse(&substitutes)?;
 
 		// Option: --help
 		self.help = matches.opt_present("help");
@@ -73,12 +499,235 @@ impl CommandlineOptions {
 			self.sleep = Some(value.unwrap());
 		};
 
-		// Free options.
-		self.free = matches.free;
+		// Option: --config
+		self.config = matches.opt_str("config");
+
+		// Option: --dry-run
+		self.dry_run = matches.opt_present("dry-run");
+
+		// Option: --preset
+		self.presets = matches.opt_strs("preset");
+
+		// Options: exclusion tags
+		self.exclude_caches = matches.opt_present("exclude-caches");
+		self.exclude_markers = matches.opt_strs("exclude-marker");
+		self.keep_tag_files = matches.opt_present("keep-tag-files");
+
+		// Options: incremental archiving
+		self.incremental = matches.opt_str("incremental");
+		self.record_deletions = matches.opt_present("record-deletions");
+
+		// Options: manifest and checksum
+		self.manifest = matches.opt_str("manifest");
+		self.checksum_file = matches.opt_present("checksum");
+
+		// Option: --sign
+		self.sign = matches.opt_str("sign");
+
+		// Option: --sort
+		self.sort = matches.opt_str("sort");
+
+		// Option: --out-of-range-time
+		self.out_of_range_time = matches.opt_str("out-of-range-time");
+
+		// Option: --non-utf8-names
+		self.non_utf8_names = matches.opt_str("non-utf8-names");
+
+		// Option: --name-encoding
+		self.name_encoding = matches.opt_str("name-encoding");
+
+		// Option: --normalize-names
+		self.normalize_names = matches.opt_str("normalize-names");
+
+		// Option: --portable
+		self.portable = matches.opt_present("portable");
+
+		// Option: --xattrs
+		self.xattrs = matches.opt_present("xattrs");
+
+		// Option: --hard-links
+		self.hard_links = matches.opt_present("hard-links");
+
+		// Option: --one-file-system
+		self.one_file_system = matches.opt_present("one-file-system");
+
+		// Option: --reproducible
+		self.reproducible = matches.opt_present("reproducible");
+
+		// Option: --same-owner
+		self.same_owner = matches.opt_present("same-owner");
+
+		// Option: --keep-special-bits
+		self.keep_special_bits = matches.opt_present("keep-special-bits");
+
+		// Options: filters on metadata
+		self.min_size = matches.opt_str("min-size");
+		self.max_size = matches.opt_str("max-size");
+		self.newer_than = matches.opt_str("newer-than");
+		self.older_than = matches.opt_str("older-than");
+		self.max_depth = parse_count(&matches, "max-depth")?;
+
+		// Option: --all
+		self.all = matches.opt_present("all");
+
+		// Options: retention
+		self.keep_last = parse_count(&matches, "keep-last")?;
+		self.keep_daily = parse_count(&matches, "keep-daily")?;
+		self.keep_weekly = parse_count(&matches, "keep-weekly")?;
+		self.keep_monthly = parse_count(&matches, "keep-monthly")?;
+		self.max_age = matches.opt_str("max-age");
+
+		// Free options. Arguments which are not valid UTF-8 are restored.
+		let free: Vec<std::ffi::OsString> = matches
+			.free
+			.iter()
+			.map(|arg| match arg.strip_prefix('\0').and_then(|e| e.parse::<usize>().ok()) {
+				None => std::ffi::OsString::from(arg),
+				Some(index) => args[index].clone(),
+			})
+			.collect();
+		if free.iter().filter(|e| e.to_str().is_none()).count() < substitutes.iter().filter(|e| e.starts_with('\0')).count() {
+			return Err("Values of options must be valid UTF-8.".into());
+		}
+
+		// Only the source may be a path which is not valid UTF-8.
+		let subcommand = matches!(
+			free.first().and_then(|e| e.to_str()),
+			Some("config") | Some("extract") | Some("keygen") | Some("verify-signature") | Some("run")
+		);
+		for (index, arg) in free.iter().enumerate() {
+			if arg.to_str().is_none() && (subcommand || index != 1) {
+				return Err(format!("Argument is not valid UTF-8. [{}]", arg.to_string_lossy()).into());
+			}
+		}
+		if !subcommand {
+			self.source = free.get(1).map(std::path::PathBuf::from);
+		}
+		self.free = free.iter().map(|e| e.to_string_lossy().to_string()).collect();
 
 		return Ok(());
 	}
 
+	/// Override settings by commandline options.
+	pub fn configure(&self, settings: &mut configuration::Settings) {
+		for name in &self.presets {
+			settings.presets.get_or_insert_with(std::collections::HashSet::new).insert(name.to_string());
+			settings.set_origin(&format!("presets[{}]", name), COMMAND_LINE);
+		}
+		if self.exclude_caches {
+			settings.exclude_caches = Some(true);
+			settings.set_origin("exclude_caches", COMMAND_LINE);
+		}
+		for name in &self.exclude_markers {
+			settings.exclude_markers.get_or_insert_with(std::collections::HashSet::new).insert(name.to_string());
+			settings.set_origin(&format!("exclude_markers[{}]", name), COMMAND_LINE);
+		}
+		if self.keep_tag_files {
+			settings.keep_tag_files = Some(true);
+			settings.set_origin("keep_tag_files", COMMAND_LINE);
+		}
+		if self.incremental.is_some() {
+			settings.incremental = self.incremental.clone();
+			settings.set_origin("incremental", COMMAND_LINE);
+		}
+		if self.record_deletions {
+			settings.record_deletions = Some(true);
+			settings.set_origin("record_deletions", COMMAND_LINE);
+		}
+		if self.manifest.is_some() {
+			settings.manifest = self.manifest.clone();
+			settings.set_origin("manifest", COMMAND_LINE);
+		}
+		if self.checksum_file {
+			settings.checksum_file = Some(true);
+			settings.set_origin("checksum_file", COMMAND_LINE);
+		}
+		if self.sign.is_some() {
+			settings.sign = self.sign.clone();
+			settings.set_origin("sign", COMMAND_LINE);
+		}
+		if self.sort.is_some() {
+			settings.sort = self.sort.clone();
+			settings.set_origin("sort", COMMAND_LINE);
+		}
+		if self.out_of_range_time.is_some() {
+			settings.out_of_range_time = self.out_of_range_time.clone();
+			settings.set_origin("out_of_range_time", COMMAND_LINE);
+		}
+		if self.non_utf8_names.is_some() {
+			settings.non_utf8_names = self.non_utf8_names.clone();
+			settings.set_origin("non_utf8_names", COMMAND_LINE);
+		}
+		if self.name_encoding.is_some() {
+			settings.name_encoding = self.name_encoding.clone();
+			settings.set_origin("name_encoding", COMMAND_LINE);
+		}
+		if self.normalize_names.is_some() {
+			settings.normalize_names = self.normalize_names.clone();
+			settings.set_origin("normalize_names", COMMAND_LINE);
+		}
+		if self.portable {
+			settings.portable = Some(true);
+			settings.set_origin("portable", COMMAND_LINE);
+		}
+		if self.xattrs {
+			settings.xattrs = Some(true);
+			settings.set_origin("xattrs", COMMAND_LINE);
+		}
+		if self.hard_links {
+			settings.hard_links = Some(true);
+			settings.set_origin("hard_links", COMMAND_LINE);
+		}
+		if self.one_file_system {
+			settings.one_file_system = Some(true);
+			settings.set_origin("one_file_system", COMMAND_LINE);
+		}
+		if self.reproducible {
+			settings.reproducible = Some(true);
+			settings.set_origin("reproducible", COMMAND_LINE);
+		}
+		if self.min_size.is_some() {
+			settings.min_size = self.min_size.clone();
+			settings.set_origin("min_size", COMMAND_LINE);
+		}
+		if self.max_size.is_some() {
+			settings.max_size = self.max_size.clone();
+			settings.set_origin("max_size", COMMAND_LINE);
+		}
+		if self.newer_than.is_some() {
+			settings.newer_than = self.newer_than.clone();
+			settings.set_origin("newer_than", COMMAND_LINE);
+		}
+		if self.older_than.is_some() {
+			settings.older_than = self.older_than.clone();
+			settings.set_origin("older_than", COMMAND_LINE);
+		}
+		if self.max_depth.is_some() {
+			settings.max_depth = self.max_depth;
+			settings.set_origin("max_depth", COMMAND_LINE);
+		}
+		if self.keep_last.is_some() {
+			settings.keep_last = self.keep_last;
+			settings.set_origin("keep_last", COMMAND_LINE);
+		}
+		if self.keep_daily.is_some() {
+			settings.keep_daily = self.keep_daily;
+			settings.set_origin("keep_daily", COMMAND_LINE);
+		}
+		if self.keep_weekly.is_some() {
+			settings.keep_weekly = self.keep_weekly;
+			settings.set_origin("keep_weekly", COMMAND_LINE);
+		}
+		if self.keep_monthly.is_some() {
+			settings.keep_monthly = self.keep_monthly;
+			settings.set_origin("keep_monthly", COMMAND_LINE);
+		}
+		if self.max_age.is_some() {
+			settings.max_age = self.max_age.clone();
+			settings.set_origin("max_age", COMMAND_LINE);
+		}
+	}
+
 	pub fn free(&self) -> &Vec<String> {
 		return &self.free;
 	}
@@ -91,21 +740,131 @@ impl CommandlineOptions {
 		eprintln!("    * Recursively compresses all files and directories under the specified path.");
 		eprintln!("    * If the specified path is a directory, the directory name is used as the root of the archive.");
 		eprintln!();
+		eprintln!("Subcommands:");
+		eprintln!("    rzip run {{job}} ...    Run named jobs in settings.toml.");
+		eprintln!("    rzip run --all        Run all jobs in settings.toml.");
+		eprintln!("    rzip config show      Print the effective settings with their source files.");
+		eprintln!("    rzip config check     Validate configuration files.");
+		eprintln!("    rzip config init      Write a starter settings.toml.");
+		eprintln!("    rzip extract {{archive}} [destination]");
+		eprintln!("                          Extract the archive. (default: current directory)");
+		eprintln!("    rzip keygen [secret key] [public key]");
+		eprintln!("                          Generate a key pair for --sign. (default: rzip.key, rzip.pub)");
+		eprintln!("    rzip verify-signature {{archive}} {{public key}} [signature]");
+		eprintln!("                          Verify the signature. (default: {{archive}}.minisig)");
+		eprintln!();
 		eprint!("{}", options.short_usage("\n    rzip \"archived.zip\" \"path to archive\""));
 		eprint!("{}", options.usage(""));
 	}
 }
 
-/// Entrypoint.
-fn main() {
-	// Configure.
-	let result = configuration::Settings::new();
+/// Parse option value as a count.
+fn parse_count(matches: &getopts::Matches, name: &str) -> std::result::Result<Option<usize>, Box<dyn std::error::Error>> {
+	let value = matches.opt_str(name);
+	if value.is_none() {
+		return Ok(None);
+	}
+	let value = value.unwrap().parse::<usize>();
+	if value.is_err() {
+		return Err(format!("Invalid value for option: '--{}'", name).into());
+	}
+	return Ok(Some(value.unwrap()));
+}
+
+/// Subcommand: config
+///
+/// # Returns
+/// Exit code.
+fn run_config_command(options: &CommandlineOptions) -> i32 {
+	let free_args = options.free();
+	let action = free_args.get(1).map(|e| e.as_str()).unwrap_or("");
+	let argument = free_args.get(2).map(|e| e.as_str()).unwrap_or("");
+
+	let result = match action {
+		// rzip config show [path to source]
+		"show" => configuration::Settings::load(options.config.as_deref(), std::path::Path::new(argument)).and_then(|mut settings| {
+			options.configure(&mut settings);
+			settings.expand_presets()?;
+			return settings.show();
+		}),
+		// rzip config check [path to source]
+		"check" => configuration::check(options.config.as_deref(), std::path::Path::new(argument)).and_then(|count| {
+			if count == 0 {
+				return Ok(());
+			}
+			return Err(format!("{} problem(s) found.", count).into());
+		}),
+		// rzip config init [path]
+		"init" => configuration::init(if argument == "" { "settings.toml" } else { argument }),
+		_ => {
+			options.usage();
+			return 1;
+		}
+	};
+
 	if result.is_err() {
-		eprintln!("[ERROR] Configuration error. reason: {}", result.err().unwrap());
-		std::process::exit(1);
+		eprintln!("[ERROR] {}", result.err().unwrap());
+		return 1;
+	}
+	return 0;
+}
+
+/// Subcommand: extract
+fn run_extract_command(options: &CommandlineOptions) -> i32 {
+	let free_args = options.free();
+	if free_args.len() < 2 {
+		options.usage();
+		return 1;
 	}
-	let settings = result.unwrap();
+	let path_to_archive = &free_args[1];
+	let destination = free_args.get(2).map(|e| e.as_str()).unwrap_or(".");
 
+	let stopwatch = util::Stopwatch::new();
+	println!("[INFO] extracting ... {} >> {}", path_to_archive, destination);
+	let result = match options.name_encoding.as_deref().map(configuration::NameEncoding::parse).transpose() {
+		Err(error) => Err(error),
+		Ok(name_encoding) => extractor::ZipExtractor::new(options.same_owner, name_encoding, options.xattrs, options.keep_special_bits).extract(path_to_archive, destination),
+	};
+	if result.is_err() {
+		eprintln!("[ERROR] Runtime error. reason: {}", result.err().unwrap());
+		return 1;
+	}
+	println!("[INFO] Ok. ({})", stopwatch);
+	return 0;
+}
+
+/// Subcommands: keygen, verify-signature
+fn run_signature_command(options: &CommandlineOptions) -> i32 {
+	let free_args = options.free();
+	let command = free_args.first().map(|e| e.as_str()).unwrap_or("");
+	let first = free_args.get(1).map(|e| e.as_str());
+	let second = free_args.get(2).map(|e| e.as_str());
+
+	let result = match command {
+		// rzip keygen [secret key] [public key]
+		"keygen" => signature::generate_key(first.unwrap_or("rzip.key"), second.unwrap_or("rzip.pub")),
+		// rzip verify-signature {archive} {public key} [signature]
+		"verify-signature" if first.is_some() && second.is_some() => {
+			signature::verify(first.unwrap(), second.unwrap(), free_args.get(3).map(|e| e.as_str())).map(|trusted_comment| {
+				println!("[INFO] Signature and comment signature verified.");
+				println!("[INFO] Trusted comment: {}", trusted_comment);
+			})
+		}
+		_ => {
+			options.usage();
+			return 1;
+		}
+	};
+
+	if result.is_err() {
+		eprintln!("[ERROR] {}", result.err().unwrap());
+		return 1;
+	}
+	return 0;
+}
+
+/// Entrypoint.
+fn main() {
 	// Parse commandline options.
 	let mut options = CommandlineOptions::new();
 	let result = options.parse();
@@ -116,6 +875,36 @@ fn main() {
 		std::process::exit(1);
 	}
 
+	// Subcommand: config
+	if options.free().first().map(|e| e.as_str()) == Some("config") {
+		std::process::exit(run_config_command(&options));
+	}
+
+	// Subcommand: extract
+	if options.free().first().map(|e| e.as_str()) == Some("extract") {
+		std::process::exit(run_extract_command(&options));
+	}
+
+	// Subcommands: keygen, verify-signature
+	if matches!(options.free().first().map(|e| e.as_str()), Some("keygen") | Some("verify-signature")) {
+		std::process::exit(run_signature_command(&options));
+	}
+
+	// Configure. The project configuration is searched from the source.
+	let source = options.source.as_deref().unwrap_or(std::path::Path::new(""));
+	let result = configuration::Settings::load(options.config.as_deref(), source);
+	if result.is_err() {
+		eprintln!("[ERROR] Configuration error. reason: {}", result.err().unwrap());
+		std::process::exit(1);
+	}
+	let mut settings = result.unwrap();
+	options.configure(&mut settings);
+	let result = settings.expand_presets();
+	if result.is_err() {
+		eprintln!("[ERROR] Configuration error. reason: {}", result.err().unwrap());
+		std::process::exit(1);
+	}
+
 	// Show usage.
 	if options.help {
 		options.usage();
@@ -131,7 +920,23 @@ fn main() {
 	// Free options.
 	let free_args = options.free();
 
-	if free_args.len() < 2 {
+	// Subcommand: run
+	if free_args.first().map(|e| e.as_str()) == Some("run") {
+		let names = if options.all { settings.job_names() } else { free_args[1..].to_vec() };
+		let stopwatch = util::Stopwatch::new();
+		let zipper = application::Zipper::new(options.dry_run);
+		let result = zipper.run_jobs(&settings, &names);
+		if result.is_err() {
+			eprintln!("[ERROR] Runtime error. reason: {}", result.err().unwrap());
+			std::thread::sleep(std::time::Duration::from_millis(milliseconds));
+			std::process::exit(1);
+		}
+		println!("[INFO] Ok. ({})", stopwatch);
+		std::thread::sleep(std::time::Duration::from_millis(milliseconds));
+		return;
+	}
+
+	if options.source.is_none() {
 		options.usage();
 		std::process::exit(1);
 	}
@@ -143,11 +948,11 @@ fn main() {
 	let path_to_archive = &free_args[0];
 
 	// 2nd argument is path to file or directory.
-	let path_to_source = &free_args[1];
+	let path_to_source = std::slice::from_ref(options.source.as_ref().unwrap());
 
 	// Compression.
-	let zipper = application::Zipper::new();
-	let result = zipper.archive(&settings, &path_to_archive, &path_to_source, create_root);
+	let zipper = application::Zipper::new(options.dry_run);
+	let result = zipper.archive(&settings, path_to_archive, path_to_source, create_root);
 	if result.is_err() {
 		eprintln!("[ERROR] Runtime error. reason: {:?}", result.err().unwrap());
 		std::thread::sleep(std::time::Duration::from_millis(milliseconds));
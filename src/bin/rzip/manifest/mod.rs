@@ -0,0 +1,125 @@
+//!
+//! Manifest of the archived entries and checksum sidecar of the archive.
+//!
+
+use crate::configuration;
+use crate::util;
+
+/// Formats of the manifest.
+pub const FORMATS: [&str; 2] = ["sha256", "json"];
+
+///
+/// Archived entry.
+///
+#[derive(serde_derive::Serialize, std::fmt::Debug)]
+pub struct ManifestEntry {
+	/// Relative path from the root. Directories end with `/`.
+	pub path: String,
+
+	/// Size in bytes.
+	pub size: u64,
+
+	/// Last modified time. (RFC 3339, UTC)
+	pub mtime: String,
+
+	/// Unix permissions. (octal, `None` on Windows)
+	pub mode: Option<String>,
+
+	/// SHA-256 of the content. (hex, `None` for directories)
+	pub sha256: Option<String>,
+}
+
+impl ManifestEntry {
+	/// Create a new instance from metadata.
+	///
+	/// # Arguments
+	/// * `path` Relative path from the root.
+	/// * `meta` Metadata of the entry.
+	/// * `mtime` Last modified time stored in the archive.
+	/// * `mode` Unix permissions stored in the archive.
+	/// * `sha256` SHA-256 of the content. `None` for directories.
+	pub fn new(path: &str, meta: &std::fs::Metadata, mtime: std::time::SystemTime, mode: Option<u32>, sha256: Option<String>) -> ManifestEntry {
+		let mtime = chrono::DateTime::<chrono::Utc>::from(mtime);
+		let instance = ManifestEntry {
+			path: path.to_string(),
+			size: if meta.is_dir() { 0 } else { meta.len() },
+			mtime: mtime.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+			mode: mode.map(|e| format!("{:04o}", e & 0o7777)),
+			sha256,
+		};
+		return instance;
+	}
+}
+
+///
+/// Manifest written into the archive.
+///
+pub struct Manifest {
+	/// Format. (`sha256` or `json`)
+	format: String,
+
+	/// Archived entries.
+	entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+	/// Create from [configuration::Settings]. Returns `None` if no manifest is configured.
+	pub fn from_settings(settings: &configuration::Settings) -> Result<Option<Manifest>, Box<dyn std::error::Error>> {
+		let format = match &settings.manifest {
+			None => return Ok(None),
+			Some(format) => format.to_lowercase(),
+		};
+		if !FORMATS.contains(&format.as_str()) {
+			return Err(format!("Unknown manifest format [{}]. ({})", format, FORMATS.join(", ")).into());
+		}
+
+		let instance = Manifest { format, entries: Vec::new() };
+		return Ok(Some(instance));
+	}
+
+	/// Record an archived entry.
+	pub fn record(&mut self, entry: ManifestEntry) {
+		self.entries.push(entry);
+	}
+
+	/// Name of the entry in the archive.
+	pub fn entry_name(&self) -> &str {
+		if self.format == "json" {
+			return "MANIFEST.json";
+		}
+		return "MANIFEST.sha256";
+	}
+
+	/// Render the manifest.
+	pub fn render(&self) -> Result<String, Box<dyn std::error::Error>> {
+		if self.format == "json" {
+			return Ok(serde_json::to_string_pretty(&self.entries)?);
+		}
+
+		// Compatible with the output of `sha256sum`.
+		let mut result = String::new();
+		for entry in &self.entries {
+			if let Some(sha256) = &entry.sha256 {
+				result.push_str(&format!("{}  {}\n", sha256, entry.path));
+			}
+		}
+		return Ok(result);
+	}
+}
+
+/// Write `archive.zip.sha256` next to the archive. (compatible with `sha256sum -c`)
+///
+/// # Arguments
+/// * `path_to_archive` Path to the archive.
+///
+/// # Returns
+/// Path to the checksum file.
+pub fn write_checksum_file(path_to_archive: &str) -> Result<String, Box<dyn std::error::Error>> {
+	use crate::util::PathHelper;
+
+	let sha256 = util::hash_file(std::path::Path::new(path_to_archive))?;
+	let name = std::path::Path::new(path_to_archive).name_as_str().to_string();
+	let path = format!("{}.sha256", path_to_archive);
+	std::fs::write(&path, format!("{}  {}\n", sha256, name))?;
+	return Ok(path);
+}
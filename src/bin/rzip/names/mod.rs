@@ -0,0 +1,402 @@
+//!
+//! Entry names which the zip writer cannot represent directly.
+//!
+//! The zip writer accepts UTF-8 names only. Names of raw bytes or legacy encodings are written in UTF-8 first,
+//! and rewritten in the finished archive by [rewrite_names].
+//!
+
+use crate::configuration;
+use crate::extra_field;
+
+/// Characters of code page 437 from 0x80 to 0xFF.
+const CP437_HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+/// Private use characters standing for invalid bytes in placeholders. (U+F780 to U+F7FF = 0x80 to 0xFF)
+const RAW_BYTE_BASE: u32 = 0xF700;
+
+///
+/// Name written in the header instead of the placeholder.
+///
+#[derive(std::fmt::Debug, std::clone::Clone)]
+pub struct RawName {
+	/// Bytes of the name.
+	pub bytes: Vec<u8>,
+
+	/// UTF-8 name stored in the Unicode path extra field. (`0x7075`)
+	pub unicode: Option<String>,
+
+	/// Set the language encoding flag. (bit 11)
+	pub utf8: bool,
+}
+
+impl RawName {
+	/// Size of the extra field added with the name. (Unicode path extra field)
+	pub fn extra_size(&self) -> usize {
+		return match &self.unicode {
+			None => 0,
+			Some(unicode) => unicode_path(&self.bytes, unicode).len(),
+		};
+	}
+}
+
+/// Bytes of the name. `None` if the platform does not provide them.
+pub fn name_bytes(name: &std::ffi::OsStr) -> Option<Vec<u8>> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::ffi::OsStrExt;
+		return Some(name.as_bytes().to_vec());
+	}
+
+	#[cfg(not(unix))]
+	{
+		let _ = name;
+		return None;
+	}
+}
+
+/// Convert bytes to [String], replacing each invalid byte by `replace`.
+fn convert(bytes: &[u8], replace: fn(u8) -> String) -> String {
+	let mut result = String::new();
+	let mut rest = bytes;
+	while !rest.is_empty() {
+		match std::str::from_utf8(rest) {
+			Ok(valid) => {
+				result.push_str(valid);
+				break;
+			}
+			Err(error) => {
+				let (valid, invalid) = rest.split_at(error.valid_up_to());
+				result.push_str(std::str::from_utf8(valid).unwrap());
+				let length = error.error_len().unwrap_or(invalid.len());
+				for byte in &invalid[..length] {
+					result.push_str(&replace(*byte));
+				}
+				rest = &invalid[length..];
+			}
+		}
+	}
+	return result;
+}
+
+/// Escape invalid bytes of the name as `%XX`.
+pub fn escape(name: &std::ffi::OsStr) -> String {
+	return match name_bytes(name) {
+		None => name.to_string_lossy().to_string(),
+		Some(bytes) => escape_bytes(&bytes),
+	};
+}
+
+/// Escape invalid bytes as `%XX`.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+	return convert(bytes, |byte| format!("%{:02X}", byte));
+}
+
+/// Name written by the zip writer for a name which is not valid UTF-8. Invalid bytes are replaced by private use characters.
+/// The bytes to store are tracked by the caller, as real names may contain these characters too.
+///
+/// # Returns
+/// `None` if the platform does not provide bytes.
+pub fn to_placeholder(name: &std::ffi::OsStr) -> Option<String> {
+	let bytes = name_bytes(name)?;
+	return Some(convert(&bytes, |byte| std::char::from_u32(RAW_BYTE_BASE + byte as u32).unwrap().to_string()));
+}
+
+/// Encode the name.
+///
+/// # Returns
+/// `None` if the encoding cannot represent the name.
+pub fn encode(name: &str, encoding: configuration::NameEncoding) -> Option<Vec<u8>> {
+	return match encoding {
+		configuration::NameEncoding::Utf8 => Some(name.as_bytes().to_vec()),
+		configuration::NameEncoding::Cp932 => {
+			let (bytes, _, unmappable) = encoding_rs::SHIFT_JIS.encode(name);
+			if unmappable {
+				None
+			} else {
+				Some(bytes.to_vec())
+			}
+		}
+		configuration::NameEncoding::Cp437 => name
+			.chars()
+			.map(|c| {
+				if c.is_ascii() {
+					Some(c as u8)
+				} else {
+					CP437_HIGH.chars().position(|e| e == c).map(|e| 0x80 + e as u8)
+				}
+			})
+			.collect(),
+	};
+}
+
+/// Decode the name.
+///
+/// # Returns
+/// `None` if the bytes are invalid in the encoding.
+pub fn decode(bytes: &[u8], encoding: configuration::NameEncoding) -> Option<String> {
+	return match encoding {
+		configuration::NameEncoding::Utf8 => std::str::from_utf8(bytes).ok().map(|e| e.to_string()),
+		configuration::NameEncoding::Cp932 => encoding_rs::SHIFT_JIS
+			.decode_without_bom_handling_and_without_replacement(bytes)
+			.map(|e| e.to_string()),
+		configuration::NameEncoding::Cp437 => Some(
+			bytes
+				.iter()
+				.map(|byte| {
+					if byte.is_ascii() {
+						*byte as char
+					} else {
+						CP437_HIGH.chars().nth((byte - 0x80) as usize).unwrap()
+					}
+				})
+				.collect(),
+		),
+	};
+}
+
+/// UTF-8 name in the Unicode path extra field.
+///
+/// # Arguments
+/// * `name` Name in the header.
+/// * `extra` Extra fields of the header.
+///
+/// # Returns
+/// `None` if the field is missing, or stale. (the name was changed after the field was written)
+pub fn find_unicode_path(name: &[u8], extra: &[u8]) -> Option<String> {
+	let data = extra_field::find(extra, extra_field::UNICODE_PATH)?;
+	if data.len() < 5 || data[0] != 1 || read_u32(data, 1) != crc32fast::hash(name) {
+		return None;
+	}
+	return std::str::from_utf8(&data[5..]).ok().map(|e| e.to_string());
+}
+
+/// Detect the encoding of the names which are neither UTF-8 nor have the Unicode path extra field.
+///
+/// # Returns
+/// `None` if no encoding decodes all of them.
+pub fn detect_encoding(names: &[&[u8]]) -> Option<configuration::NameEncoding> {
+	let candidates = [configuration::NameEncoding::Utf8, configuration::NameEncoding::Cp932];
+	return candidates.iter().copied().find(|encoding| names.iter().all(|name| decode(name, *encoding).is_some()));
+}
+
+/// Read the general purpose flags in the local header of an entry.
+///
+/// # Arguments
+/// * `file` Archive.
+/// * `header_start` Offset of the local header.
+pub fn read_flags(file: &mut std::fs::File, header_start: u64) -> Result<u16, Box<dyn std::error::Error>> {
+	use std::io::{Read, Seek};
+
+	let mut header = [0u8; 8];
+	file.seek(std::io::SeekFrom::Start(header_start))?;
+	file.read_exact(&mut header)?;
+	if header[0..4] != [0x50, 0x4b, 0x03, 0x04] {
+		return Err("Invalid local file header.".into());
+	}
+	return Ok(read_u16(&header, 6));
+}
+
+/// Decode the name of an entry.
+///
+/// # Arguments
+/// * `name` Name in the header.
+/// * `flags` General purpose flags.
+/// * `extra` Extra fields of the header.
+/// * `encoding` Encoding of the names without the language encoding flag. `None` if unknown.
+///
+/// # Returns
+/// `None` if the name cannot be decoded.
+pub fn decode_name(name: &[u8], flags: u16, extra: &[u8], encoding: Option<configuration::NameEncoding>) -> Option<String> {
+	if flags & 0x0800 != 0 {
+		return Some(String::from_utf8_lossy(name).to_string());
+	}
+	if let Some(unicode) = find_unicode_path(name, extra) {
+		return Some(unicode);
+	}
+	if name.is_ascii() {
+		return Some(String::from_utf8_lossy(name).to_string());
+	}
+	return decode(name, encoding?);
+}
+
+/// Rewrite names of the entries in the finished archive.
+///
+/// # Arguments
+/// * `path_to_archive` Path to the archive.
+/// * `renames` Names to rewrite. (name written by the zip writer, name to store)
+pub fn rewrite_names(path_to_archive: &str, renames: &std::collections::HashMap<String, RawName>) -> Result<(), Box<dyn std::error::Error>> {
+	let temporary_path = format!("{}.tmp", path_to_archive);
+	let result = write_renamed_archive(path_to_archive, &temporary_path, renames);
+	if result.is_err() {
+		let _ = std::fs::remove_file(&temporary_path);
+		return result;
+	}
+
+	std::fs::rename(&temporary_path, path_to_archive)?;
+	return Ok(());
+}
+
+/// Write a copy of the archive with the names rewritten.
+///
+/// # Arguments
+/// * `path_to_archive` Path to the archive.
+/// * `temporary_path` Path to the copy.
+/// * `renames` Names to rewrite. (name written by the zip writer, name to store)
+fn write_renamed_archive(path_to_archive: &str, temporary_path: &str, renames: &std::collections::HashMap<String, RawName>) -> Result<(), Box<dyn std::error::Error>> {
+	use std::io::{Read, Seek, Write};
+
+	let mut source = std::fs::File::open(path_to_archive)?;
+	let length = source.metadata()?.len();
+
+	// End of central directory record
+	let tail_length = std::cmp::min(length, 22 + 65535);
+	let mut tail = vec![0u8; tail_length as usize];
+	source.seek(std::io::SeekFrom::Start(length - tail_length))?;
+	source.read_exact(&mut tail)?;
+	let position = (0..tail.len().saturating_sub(21)).rev().find(|i| tail[*i..*i + 4] == [0x50, 0x4b, 0x05, 0x06]);
+	if position.is_none() {
+		return Err(format!("End of central directory not found. [{}]", path_to_archive).into());
+	}
+	let end = tail[position.unwrap()..].to_vec();
+	let count = read_u16(&end, 10) as usize;
+	let central_size = read_u32(&end, 12);
+	let central_offset = read_u32(&end, 16);
+	if count == 0xFFFF || central_size == 0xFFFF_FFFF || central_offset == 0xFFFF_FFFF {
+		return Err("Names cannot be rewritten in ZIP64 archives. (over 4 GiB or 65535 entries)".into());
+	}
+
+	// Central directory
+	let mut central = vec![0u8; central_size as usize];
+	source.seek(std::io::SeekFrom::Start(central_offset as u64))?;
+	source.read_exact(&mut central)?;
+
+	let mut target = std::io::BufWriter::new(std::fs::File::create(temporary_path)?);
+	let mut written: u64 = 0;
+	let mut new_central: Vec<u8> = Vec::new();
+
+	let mut offset = 0;
+	for _ in 0..count {
+		let header = &central[offset..offset + 46];
+		if header[0..4] != [0x50, 0x4b, 0x01, 0x02] {
+			return Err(format!("Invalid central directory. [{}]", path_to_archive).into());
+		}
+		let name_length = read_u16(header, 28) as usize;
+		let extra_length = read_u16(header, 30) as usize;
+		let comment_length = read_u16(header, 32) as usize;
+		let name = &central[offset + 46..offset + 46 + name_length];
+		let extra = &central[offset + 46 + name_length..offset + 46 + name_length + extra_length];
+		let comment = &central[offset + 46 + name_length + extra_length..offset + 46 + name_length + extra_length + comment_length];
+		let compressed_size = read_u32(header, 20);
+		let local_offset = read_u32(header, 42);
+		let flags = read_u16(header, 8);
+		if compressed_size == 0xFFFF_FFFF || local_offset == 0xFFFF_FFFF || flags & 0x0008 != 0 {
+			return Err("Names cannot be rewritten in ZIP64 archives or entries with data descriptors.".into());
+		}
+
+		// New name, flags and Unicode path extra field.
+		let (new_name, new_flags, unicode_extra) = match renames.get(&String::from_utf8_lossy(name).to_string()) {
+			None => (name.to_vec(), flags, Vec::new()),
+			Some(raw) => {
+				let flags = if raw.utf8 { flags | 0x0800 } else { flags & !0x0800 };
+				let extra = match &raw.unicode {
+					None => Vec::new(),
+					Some(unicode) => unicode_path(&raw.bytes, unicode),
+				};
+				(raw.bytes.clone(), flags, extra)
+			}
+		};
+
+		// Local file header
+		let mut local = [0u8; 30];
+		source.seek(std::io::SeekFrom::Start(local_offset as u64))?;
+		source.read_exact(&mut local)?;
+		let local_name_length = read_u16(&local, 26) as i64;
+		let local_extra_length = read_u16(&local, 28) as usize;
+		source.seek(std::io::SeekFrom::Current(local_name_length))?;
+		let mut local_extra = vec![0u8; local_extra_length];
+		source.read_exact(&mut local_extra)?;
+
+		if extra_field::MAX_EXTRA_SIZE < local_extra.len() + unicode_extra.len() || extra_field::MAX_EXTRA_SIZE < extra.len() + unicode_extra.len() {
+			let message = format!(
+				"No room for the Unicode path extra field of [{}]. (extra fields over 65535 bytes)",
+				String::from_utf8_lossy(name)
+			);
+			return Err(message.into());
+		}
+
+		let new_local_offset = written;
+		write_u16(&mut local, 6, new_flags);
+		write_u16(&mut local, 26, new_name.len() as u16);
+		write_u16(&mut local, 28, (local_extra.len() + unicode_extra.len()) as u16);
+		target.write_all(&local)?;
+		target.write_all(&new_name)?;
+		target.write_all(&local_extra)?;
+		target.write_all(&unicode_extra)?;
+		let copied = std::io::copy(&mut (&mut source).take(compressed_size as u64), &mut target)?;
+		written += (local.len() + new_name.len() + local_extra.len() + unicode_extra.len()) as u64 + copied;
+
+		// Central directory header
+		let mut header = header.to_vec();
+		write_u16(&mut header, 8, new_flags);
+		write_u16(&mut header, 28, new_name.len() as u16);
+		write_u16(&mut header, 30, (extra.len() + unicode_extra.len()) as u16);
+		write_u32(&mut header, 42, new_local_offset as u32);
+		new_central.extend_from_slice(&header);
+		new_central.extend_from_slice(&new_name);
+		new_central.extend_from_slice(extra);
+		new_central.extend_from_slice(&unicode_extra);
+		new_central.extend_from_slice(comment);
+
+		offset += 46 + name_length + extra_length + comment_length;
+	}
+
+	if 0xFFFF_FFFF <= written + new_central.len() as u64 {
+		return Err("Names cannot be rewritten in ZIP64 archives. (over 4 GiB or 65535 entries)".into());
+	}
+
+	// End of central directory record
+	let mut end = end;
+	write_u32(&mut end, 12, new_central.len() as u32);
+	write_u32(&mut end, 16, written as u32);
+	target.write_all(&new_central)?;
+	target.write_all(&end)?;
+	target.flush()?;
+	return Ok(());
+}
+
+/// Encode the Unicode path extra field.
+///
+/// # Arguments
+/// * `name` Name in the header.
+/// * `unicode` UTF-8 name.
+fn unicode_path(name: &[u8], unicode: &str) -> Vec<u8> {
+	let mut data: Vec<u8> = vec![1];
+	data.extend_from_slice(&crc32fast::hash(name).to_le_bytes());
+	data.extend_from_slice(unicode.as_bytes());
+
+	let mut result: Vec<u8> = Vec::new();
+	result.extend_from_slice(&extra_field::UNICODE_PATH.to_le_bytes());
+	result.extend_from_slice(&(data.len() as u16).to_le_bytes());
+	result.extend_from_slice(&data);
+	return result;
+}
+
+/// Read little endian u16.
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+	return u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+}
+
+/// Read little endian u32.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+	return u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+}
+
+/// Write little endian u16.
+fn write_u16(bytes: &mut [u8], offset: usize, value: u16) {
+	bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Write little endian u32.
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+	bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
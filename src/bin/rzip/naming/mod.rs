@@ -0,0 +1,425 @@
+//!
+//! Naming template for the path of archive.
+//!
+//! * `{date:%Y%m%d}` Local time with any chrono format. (default: `%Y%m%d`)
+//! * `{utc:%Y%m%d}` UTC time with any chrono format. (default: `%Y%m%d`)
+//! * `{host}`, `{user}` Host name and user name.
+//! * `{git:branch}`, `{git:commit}`, `{git:tag}` Git information of the source.
+//! * `{seq}`, `{seq:3}` Sequence number which avoids existing files.
+//! * `{name}`, `{version}` Name and version from the project manifest of the source.
+//! * `{0}`, `{Y}`, `{m}`, `{d}`, `{H}`, `{M}`, `{S}` and `%0`, `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` (compatible)
+//! * `{{`, `}}`, `%%` Literal braces and percent.
+//!
+
+use crate::project;
+use crate::util;
+
+/// Token of template.
+#[derive(std::fmt::Debug, std::clone::Clone)]
+enum Token {
+	/// Literal text.
+	Literal(String),
+
+	/// Placeholder. (key, argument)
+	Field(String, Option<String>),
+}
+
+/// Keys of the compatible keywords. `%Y` and `{Y}` are equivalent.
+const COMPATIBLE_KEYS: [char; 7] = ['Y', 'm', 'd', 'H', 'M', 'S', '0'];
+
+///
+/// Values referred by the template.
+///
+pub struct NamingContext {
+	/// Name of the source. (`{0}`)
+	pub name: String,
+
+	/// Path to the source.
+	pub source: std::path::PathBuf,
+
+	/// Timestamp.
+	pub now: chrono::DateTime<chrono::Local>,
+
+	/// Sequence number. (`{seq}`)
+	pub sequence: u32,
+}
+
+impl NamingContext {
+	/// Create a new instance.
+	///
+	/// # Arguments
+	/// * `name` Name of the source.
+	/// * `source` Path to the source.
+	pub fn new(name: &str, source: &std::path::Path) -> NamingContext {
+		return NamingContext {
+			name: name.to_string(),
+			source: source.to_path_buf(),
+			now: chrono::Local::now(),
+			sequence: 1,
+		};
+	}
+}
+
+///
+/// Parsed naming template.
+///
+#[derive(std::fmt::Debug, std::clone::Clone)]
+pub struct NameTemplate {
+	/// Tokens.
+	tokens: Vec<Token>,
+}
+
+impl NameTemplate {
+	/// Parse template.
+	///
+	/// # Arguments
+	/// * `template` Template string like `"{0}-{date:%Y%m%d}.zip"`
+	pub fn parse(template: &str) -> Result<NameTemplate, Box<dyn std::error::Error>> {
+		let mut tokens: Vec<Token> = Vec::new();
+		let mut literal = String::new();
+		let mut chars = template.chars().peekable();
+
+		while let Some(c) = chars.next() {
+			if c == '%' {
+				match chars.peek() {
+					Some('%') => {
+						chars.next();
+						literal.push('%');
+					}
+					Some(key) if COMPATIBLE_KEYS.contains(key) => {
+						let key = chars.next().unwrap();
+						flush_literal(&mut tokens, &mut literal);
+						tokens.push(Token::Field(key.to_string(), None));
+					}
+					_ => literal.push(c),
+				}
+			} else if c == '{' {
+				if chars.peek() == Some(&'{') {
+					chars.next();
+					literal.push('{');
+					continue;
+				}
+				let mut inner = String::new();
+				let mut closed = false;
+				for c in chars.by_ref() {
+					if c == '}' {
+						closed = true;
+						break;
+					}
+					inner.push(c);
+				}
+				if !closed {
+					return Err(format!("Unclosed '{{' in template. [{}]", template).into());
+				}
+				flush_literal(&mut tokens, &mut literal);
+				tokens.push(parse_field(&inner)?);
+			} else if c == '}' {
+				if chars.peek() == Some(&'}') {
+					chars.next();
+				}
+				literal.push('}');
+			} else {
+				literal.push(c);
+			}
+		}
+		flush_literal(&mut tokens, &mut literal);
+
+		return Ok(NameTemplate { tokens });
+	}
+
+	/// Returns `true` if the template contains a sequence number.
+	pub fn has_sequence(&self) -> bool {
+		for token in &self.tokens {
+			if let Token::Field(key, _) = token {
+				if key == "seq" {
+					return true;
+				}
+			}
+		}
+		return false;
+	}
+
+	/// Make regular expression matching any path rendered from the template at any time.
+	///
+	/// # Arguments
+	/// * `context` Values which do not change between runs. (name, host, user, branch)
+	///
+	/// # Returns
+	/// Error if the template has a keyword which cannot be matched exactly. (`{git:tag}`, `{version}`, ...)
+	pub fn to_regex(&self, context: &NamingContext) -> Result<regex::Regex, Box<dyn std::error::Error>> {
+		let mut pattern = String::from("^");
+		for token in &self.tokens {
+			match token {
+				Token::Literal(text) => pattern.push_str(&regex::escape(text)),
+				Token::Field(key, arg) => match (key.as_str(), arg.as_deref()) {
+					("0", _) | ("host", _) | ("user", _) | ("name", _) | ("git", Some("branch")) => {
+						pattern.push_str(&regex::escape(&render_field(key, arg.as_deref(), context)?))
+					}
+					("Y", _) => pattern.push_str("\\d{4}"),
+					("m", _) | ("d", _) | ("H", _) | ("M", _) | ("S", _) => pattern.push_str("\\d{2}"),
+					("date", format) | ("utc", format) => pattern.push_str(&time_format_to_regex(format.unwrap_or("%Y%m%d"))?),
+					("git", Some("commit")) => pattern.push_str("[0-9a-f]{4,40}"),
+					("seq", _) => pattern.push_str("\\d+"),
+					_ => {
+						let field = match arg {
+							None => key.to_string(),
+							Some(arg) => format!("{}:{}", key, arg),
+						};
+						return Err(format!("Keyword [{{{}}}] cannot be used with retention. Archives of other runs cannot be told apart.", field).into());
+					}
+				},
+			}
+		}
+		pattern.push('$');
+		return Ok(regex::Regex::new(&pattern)?);
+	}
+
+	/// Render the template.
+	///
+	/// # Arguments
+	/// * `context` Values referred by the template.
+	pub fn render(&self, context: &NamingContext) -> Result<String, Box<dyn std::error::Error>> {
+		let mut result = String::new();
+		for token in &self.tokens {
+			match token {
+				Token::Literal(text) => result.push_str(text),
+				Token::Field(key, arg) => result.push_str(&render_field(key, arg.as_deref(), context)?),
+			}
+		}
+		return Ok(result);
+	}
+}
+
+/// Move the pending literal into tokens.
+fn flush_literal(tokens: &mut Vec<Token>, literal: &mut String) {
+	if literal.is_empty() {
+		return;
+	}
+	tokens.push(Token::Literal(std::mem::take(literal)));
+}
+
+/// Parse inside of `{...}`.
+fn parse_field(inner: &str) -> Result<Token, Box<dyn std::error::Error>> {
+	let (key, arg) = match inner.find(':') {
+		Some(pos) => (&inner[..pos], Some(inner[pos + 1..].to_string())),
+		None => (inner, None),
+	};
+
+	match key {
+		"Y" | "m" | "d" | "H" | "M" | "S" | "0" | "host" | "user" | "name" | "version" => {}
+		"date" | "utc" => {
+			let format = arg.as_deref().unwrap_or("%Y%m%d");
+			validate_time_format(format)?;
+		}
+		"git" => match arg.as_deref() {
+			Some("branch") | Some("commit") | Some("tag") => {}
+			_ => return Err(format!("Unknown git keyword in template. [{{{}}}] (branch, commit or tag)", inner).into()),
+		},
+		"seq" => {
+			if let Some(width) = &arg {
+				if width.parse::<usize>().is_err() {
+					return Err(format!("Invalid width of sequence number. [{{{}}}]", inner).into());
+				}
+			}
+		}
+		_ => return Err(format!("Unknown keyword in template. [{{{}}}]", inner).into()),
+	}
+
+	return Ok(Token::Field(key.to_string(), arg));
+}
+
+/// Validate chrono format string.
+fn validate_time_format(format: &str) -> Result<(), Box<dyn std::error::Error>> {
+	for item in chrono::format::StrftimeItems::new(format) {
+		if let chrono::format::Item::Error = item {
+			return Err(format!("Invalid date format in template. [{}]", format).into());
+		}
+	}
+	return Ok(());
+}
+
+/// Make regular expression matching any time rendered with the chrono format.
+///
+/// # Returns
+/// Error if the format has a specifier which cannot be matched exactly.
+fn time_format_to_regex(format: &str) -> Result<String, Box<dyn std::error::Error>> {
+	use chrono::format::{Fixed, Item, Numeric, Pad};
+
+	let mut pattern = String::new();
+	for item in chrono::format::StrftimeItems::new(format) {
+		let width = match &item {
+			Item::Literal(text) | Item::Space(text) => {
+				pattern.push_str(&regex::escape(text));
+				continue;
+			}
+			Item::OwnedLiteral(text) | Item::OwnedSpace(text) => {
+				pattern.push_str(&regex::escape(text));
+				continue;
+			}
+			Item::Numeric(numeric, pad) => {
+				let width = match numeric {
+					Numeric::Year | Numeric::IsoYear => 4,
+					Numeric::YearDiv100 | Numeric::YearMod100 | Numeric::IsoYearDiv100 | Numeric::IsoYearMod100 => 2,
+					Numeric::Month | Numeric::Day | Numeric::WeekFromSun | Numeric::WeekFromMon | Numeric::IsoWeek => 2,
+					Numeric::Hour | Numeric::Hour12 | Numeric::Minute | Numeric::Second => 2,
+					Numeric::NumDaysFromSun | Numeric::WeekdayFromMon => 1,
+					Numeric::Ordinal => 3,
+					Numeric::Nanosecond => 9,
+					_ => 0,
+				};
+				match pad {
+					_ if width == 0 => 0,
+					Pad::Zero => width,
+					Pad::Space => {
+						pattern.push_str(&format!(" {{0,{}}}\\d{{1,{}}}", width - 1, width));
+						continue;
+					}
+					Pad::None => {
+						pattern.push_str(&format!("\\d{{1,{}}}", width));
+						continue;
+					}
+				}
+			}
+			Item::Fixed(fixed) => {
+				let fixed_pattern = match fixed {
+					Fixed::ShortMonthName | Fixed::ShortWeekdayName => "[A-Z][a-z]{2}",
+					Fixed::LongMonthName | Fixed::LongWeekdayName => "[A-Z][a-z]+",
+					Fixed::LowerAmPm => "[ap]m",
+					Fixed::UpperAmPm => "[AP]M",
+					Fixed::TimezoneOffset => "[+-]\\d{4}",
+					Fixed::TimezoneOffsetColon => "[+-]\\d{2}:\\d{2}",
+					_ => "",
+				};
+				if fixed_pattern != "" {
+					pattern.push_str(fixed_pattern);
+					continue;
+				}
+				0
+			}
+			Item::Error => 0,
+		};
+		if width == 0 {
+			return Err(format!("Date format [{}] cannot be used with retention. Use numeric specifiers like %Y, %m, %d.", format).into());
+		}
+		pattern.push_str(&format!("\\d{{{}}}", width));
+	}
+	return Ok(pattern);
+}
+
+/// Render a placeholder.
+fn render_field(key: &str, arg: Option<&str>, context: &NamingContext) -> Result<String, Box<dyn std::error::Error>> {
+	let now = &context.now;
+
+	let value = match key {
+		"Y" => now.format("%Y").to_string(),
+		"m" => now.format("%m").to_string(),
+		"d" => now.format("%d").to_string(),
+		"H" => now.format("%H").to_string(),
+		"M" => now.format("%M").to_string(),
+		"S" => now.format("%S").to_string(),
+		"0" => context.name.clone(),
+		"date" => now.format(arg.unwrap_or("%Y%m%d")).to_string(),
+		"utc" => now.with_timezone(&chrono::Utc).format(arg.unwrap_or("%Y%m%d")).to_string(),
+		"host" => sanitize(&detect_host_name()?),
+		"user" => sanitize(&detect_user_name()?),
+		"git" => sanitize(&git_describe(&context.source, arg.unwrap_or(""))?),
+		"name" => sanitize(&project::ProjectInfo::detect(&context.source)?.name),
+		"version" => sanitize(&project::ProjectInfo::detect(&context.source)?.version),
+		"seq" => {
+			let width = arg.unwrap_or("1").parse::<usize>()?;
+			format!("{:0>width$}", context.sequence, width = width)
+		}
+		_ => return Err(format!("Unknown keyword in template. [{}]", key).into()),
+	};
+
+	return Ok(value);
+}
+
+/// Replace characters which cannot be a part of file name.
+fn sanitize(value: &str) -> String {
+	let mut result = String::new();
+	for c in value.chars() {
+		match c {
+			'/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => result.push('-'),
+			_ => result.push(c),
+		}
+	}
+	return result;
+}
+
+/// Detect the host name.
+fn detect_host_name() -> Result<String, Box<dyn std::error::Error>> {
+	// (Windows)
+	let name = util::get_env("COMPUTERNAME");
+	if name != "" {
+		return Ok(name);
+	}
+
+	// (Linux)
+	let name = util::get_env("HOSTNAME");
+	if name != "" {
+		return Ok(name);
+	}
+	for path in ["/proc/sys/kernel/hostname", "/etc/hostname"] {
+		if let Ok(content) = util::read_text_file_all(path) {
+			let name = content.trim();
+			if name != "" {
+				return Ok(name.to_string());
+			}
+		}
+	}
+
+	return execute_command("hostname", &[]);
+}
+
+/// Detect the user name.
+fn detect_user_name() -> Result<String, Box<dyn std::error::Error>> {
+	for name in ["USERNAME", "USER", "LOGNAME"] {
+		let value = util::get_env(name);
+		if value != "" {
+			return Ok(value);
+		}
+	}
+
+	let result = execute_command("whoami", &[]);
+	if result.is_err() {
+		return Err("Cannot detect the user name.".into());
+	}
+	return result;
+}
+
+/// Retrieve git information of the source.
+///
+/// # Arguments
+/// * `source` Path to the source.
+/// * `what` One of `branch`, `commit` or `tag`.
+fn git_describe(source: &std::path::Path, what: &str) -> Result<String, Box<dyn std::error::Error>> {
+	let dir = if source.is_dir() { source } else { source.parent().unwrap_or(source) };
+
+	let args: &[&str] = match what {
+		"branch" => &["rev-parse", "--abbrev-ref", "HEAD"],
+		"commit" => &["rev-parse", "--short", "HEAD"],
+		"tag" => &["describe", "--tags", "--abbrev=0"],
+		_ => return Err(format!("Unknown git keyword. [{}]", what).into()),
+	};
+
+	let mut full_args = vec![std::ffi::OsStr::new("-C"), dir.as_os_str()];
+	full_args.extend(args.iter().map(std::ffi::OsStr::new));
+	let result = execute_command("git", &full_args);
+	if result.is_err() {
+		return Err(format!("Cannot retrieve git {} of [{}]. reason: {}", what, source.display(), result.err().unwrap()).into());
+	}
+	return result;
+}
+
+/// Execute a command and returns the first line of its output.
+fn execute_command(command: &str, args: &[&std::ffi::OsStr]) -> Result<String, Box<dyn std::error::Error>> {
+	let output = std::process::Command::new(command).args(args).output()?;
+	if !output.status.success() {
+		let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+		return Err(format!("[{}] exited with {}. {}", command, output.status, message).into());
+	}
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	let line = stdout.lines().next().unwrap_or("").trim().to_string();
+	return Ok(line);
+}
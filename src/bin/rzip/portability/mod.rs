@@ -0,0 +1,106 @@
+//!
+//! Portability of entry names to Windows and macOS.
+//!
+
+use crate::configuration;
+
+/// Device names reserved by Windows. Also with an extension. ("NUL.txt")
+const RESERVED_NAMES: [&str; 22] = [
+	"CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+	"LPT9",
+];
+
+/// Characters not allowed in names on Windows.
+const INVALID_CHARACTERS: [char; 8] = [':', '*', '?', '"', '<', '>', '|', '\\'];
+
+/// Maximum length of paths on Windows. (MAX_PATH)
+const MAX_PATH: usize = 260;
+
+///
+/// Check of entry names which break on Windows or macOS.
+///
+pub struct PortabilityLint {
+	/// Names which are not portable are errors.
+	enforce: bool,
+
+	/// Names of the entries in lowercase, and the names as they are. To detect case-insensitive collisions.
+	lowercase_names: std::collections::HashMap<String, String>,
+
+	/// Number of the entries which are not portable.
+	problems: usize,
+}
+
+impl PortabilityLint {
+	/// Create lint from [configuration::Settings].
+	pub fn from_settings(settings: &configuration::Settings) -> PortabilityLint {
+		return PortabilityLint {
+			enforce: settings.portable == Some(true),
+			lowercase_names: std::collections::HashMap::new(),
+			problems: 0,
+		};
+	}
+
+	/// Check the name of an entry. Problems are printed as warnings.
+	///
+	/// # Arguments
+	/// * `internal_path` Relative path from the root. "path/to/name"
+	/// * `name` Name of the entry.
+	/// * `display` Printable form of `internal_path`. (raw bytes as `%XX`)
+	///
+	/// # Returns
+	/// Error if the name is not portable and `--portable` is specified.
+	pub fn check(&mut self, internal_path: &str, name: &str, display: &str) -> Result<(), Box<dyn std::error::Error>> {
+		let mut problems = check_name(name);
+
+		if MAX_PATH < internal_path.encode_utf16().count() {
+			problems.push(format!("longer than {} characters", MAX_PATH));
+		}
+
+		// Unicode lowercase mapping approximates the case folding of NTFS and APFS. (e.g. `ß` and `ẞ` differ)
+		let lowercase = internal_path.to_lowercase();
+		match self.lowercase_names.get(&lowercase) {
+			None => {
+				self.lowercase_names.insert(lowercase, display.to_string());
+			}
+			Some(other) => problems.push(format!("same as {} ignoring case", other)),
+		}
+
+		if problems.is_empty() {
+			return Ok(());
+		}
+		self.problems += 1;
+
+		let message = format!("{} is not portable. ({})", display, problems.join(", "));
+		if self.enforce {
+			return Err(message.into());
+		}
+		println!("[WARN] {}", message);
+		return Ok(());
+	}
+
+	/// Number of the entries which are not portable.
+	pub fn problems(&self) -> usize {
+		return self.problems;
+	}
+}
+
+/// Problems of a name on Windows.
+fn check_name(name: &str) -> Vec<String> {
+	let mut problems: Vec<String> = Vec::new();
+
+	let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+	if RESERVED_NAMES.iter().any(|e| e.eq_ignore_ascii_case(stem)) {
+		problems.push(format!("reserved name {}", stem.to_uppercase()));
+	}
+
+	if name.ends_with('.') || name.ends_with(' ') {
+		problems.push("trailing dot or space".to_string());
+	}
+
+	let invalid: String = name.chars().filter(|c| INVALID_CHARACTERS.contains(c) || c.is_ascii_control()).collect();
+	if !invalid.is_empty() {
+		problems.push(format!("invalid characters {:?}", invalid));
+	}
+
+	return problems;
+}
@@ -0,0 +1,132 @@
+//!
+//! Project manifest (Cargo.toml, package.json, pyproject.toml) detection.
+//!
+
+use crate::util;
+
+/// Supported manifests in priority order.
+const MANIFESTS: [&str; 3] = ["Cargo.toml", "package.json", "pyproject.toml"];
+
+///
+/// Name and version of the project.
+///
+#[derive(std::fmt::Debug, std::clone::Clone)]
+pub struct ProjectInfo {
+	/// Name of the project.
+	pub name: String,
+
+	/// Version of the project.
+	pub version: String,
+}
+
+impl ProjectInfo {
+	/// Detect the manifest in the source directory and read it.
+	///
+	/// # Arguments
+	/// * `source` Path to the source directory. (or a file in it)
+	pub fn detect(source: &std::path::Path) -> Result<ProjectInfo, Box<dyn std::error::Error>> {
+		let dir = if source.is_dir() { source } else { source.parent().unwrap_or(source) };
+
+		for name in MANIFESTS {
+			let manifest = dir.join(name);
+			if !manifest.is_file() {
+				continue;
+			}
+			let manifest = match manifest.to_str() {
+				None => return Err(format!("Path is not valid UTF-8 [{}].", manifest.display()).into()),
+				Some(manifest) => manifest.to_string(),
+			};
+			let result = match name {
+				"Cargo.toml" => read_cargo_toml(&manifest),
+				"package.json" => read_package_json(&manifest),
+				_ => read_pyproject_toml(&manifest),
+			};
+			if result.is_err() {
+				return Err(format!("Invalid manifest [{}]. reason: {}", &manifest, result.err().unwrap()).into());
+			}
+			let (name, version) = result.unwrap();
+			return Ok(ProjectInfo { name, version });
+		}
+
+		let message = format!("No project manifest ({}) found in [{}].", MANIFESTS.join(", "), dir.display());
+		return Err(message.into());
+	}
+}
+
+/// Retrieve a string value at `keys` from toml.
+fn toml_str<'a>(value: &'a toml::Value, keys: &[&str]) -> Option<&'a str> {
+	let mut current = value;
+	for key in keys {
+		current = current.get(key)?;
+	}
+	return current.as_str();
+}
+
+/// Read name and version from Cargo.toml.
+fn read_cargo_toml(path: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+	let content = util::read_text_file_all(path)?;
+	let value: toml::Value = toml::from_str(&content)?;
+
+	let name = toml_str(&value, &["package", "name"]).ok_or("package.name not found.")?;
+
+	// version.workspace = true refers [workspace.package] of the parent manifest.
+	let inherited = value
+		.get("package")
+		.and_then(|e| e.get("version"))
+		.and_then(|e| e.get("workspace"))
+		.and_then(|e| e.as_bool());
+	let version = if inherited == Some(true) {
+		read_workspace_version(path)?
+	} else {
+		toml_str(&value, &["package", "version"]).ok_or("package.version not found.")?.to_string()
+	};
+
+	return Ok((name.to_string(), version));
+}
+
+/// Find version in [workspace.package] of the ancestors.
+fn read_workspace_version(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+	let mut dir = std::path::Path::new(path).parent();
+	while let Some(current) = dir {
+		let manifest = current.join("Cargo.toml");
+		if manifest.is_file() {
+			let content = util::read_text_file_all(manifest.to_str().unwrap())?;
+			let value: toml::Value = toml::from_str(&content)?;
+			if let Some(version) = toml_str(&value, &["workspace", "package", "version"]) {
+				return Ok(version.to_string());
+			}
+		}
+		dir = current.parent();
+	}
+	return Err("workspace.package.version not found.".into());
+}
+
+/// Read name and version from package.json.
+fn read_package_json(path: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+	let content = util::read_text_file_all(path)?;
+	let value: serde_json::Value = serde_json::from_str(&content)?;
+
+	let name = value.get("name").and_then(|e| e.as_str()).ok_or("name not found.")?;
+	let version = value.get("version").and_then(|e| e.as_str()).ok_or("version not found.")?;
+
+	// Scoped package like "@scope/name".
+	let name = name.trim_start_matches('@').replace('/', "-");
+
+	return Ok((name, version.to_string()));
+}
+
+/// Read name and version from pyproject.toml. ([project] or [tool.poetry])
+fn read_pyproject_toml(path: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+	let content = util::read_text_file_all(path)?;
+	let value: toml::Value = toml::from_str(&content)?;
+
+	for keys in [&["project"][..], &["tool", "poetry"][..]] {
+		let name = toml_str(&value, &[keys, &["name"]].concat());
+		let version = toml_str(&value, &[keys, &["version"]].concat());
+		if let (Some(name), Some(version)) = (name, version) {
+			return Ok((name.to_string(), version.to_string()));
+		}
+	}
+
+	return Err("name and version not found in [project] or [tool.poetry]. (dynamic version is not supported)".into());
+}
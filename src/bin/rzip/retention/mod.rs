@@ -0,0 +1,220 @@
+//!
+//! Retention policy of timestamped archives.
+//!
+
+use crate::configuration;
+use crate::naming;
+use crate::util;
+
+/// Files written next to the archive. (`--checksum` and `--sign`)
+const SIDECAR_EXTENSIONS: [&str; 2] = [".sha256", ".minisig"];
+
+///
+/// Earlier archive found by the naming template.
+///
+struct Generation {
+	/// Path to the archive.
+	path: String,
+
+	/// Last modified time.
+	time: chrono::DateTime<chrono::Local>,
+}
+
+///
+/// Retention policy
+///
+pub struct RetentionPolicy {
+	/// Keep the last N archives.
+	keep_last: Option<usize>,
+
+	/// Keep the last archive of N days.
+	keep_daily: Option<usize>,
+
+	/// Keep the last archive of N weeks.
+	keep_weekly: Option<usize>,
+
+	/// Keep the last archive of N months.
+	keep_monthly: Option<usize>,
+
+	/// Remove archives older than this.
+	max_age: Option<std::time::Duration>,
+}
+
+impl RetentionPolicy {
+	/// Create policy from [configuration::Settings]. Returns `None` if no retention is configured.
+	pub fn from_settings(settings: &configuration::Settings) -> Result<Option<RetentionPolicy>, Box<dyn std::error::Error>> {
+		let max_age = match &settings.max_age {
+			None => None,
+			Some(value) => Some(util::parse_duration(value)?),
+		};
+
+		let policy = RetentionPolicy {
+			keep_last: settings.keep_last,
+			keep_daily: settings.keep_daily,
+			keep_weekly: settings.keep_weekly,
+			keep_monthly: settings.keep_monthly,
+			max_age,
+		};
+		if !policy.has_keep_rules() && policy.max_age.is_none() {
+			return Ok(None);
+		}
+
+		return Ok(Some(policy));
+	}
+
+	/// Returns `true` if any of `keep_*` is specified.
+	fn has_keep_rules(&self) -> bool {
+		return self.keep_last.is_some() || self.keep_daily.is_some() || self.keep_weekly.is_some() || self.keep_monthly.is_some();
+	}
+
+	/// Remove expired archives matching the same template.
+	///
+	/// # Arguments
+	/// * `template` Naming template of the archive.
+	/// * `context` Values used to render `current`.
+	/// * `current` Path to the archive just created. It is never removed.
+	/// * `dry_run` Only list archives to remove.
+	pub fn apply(&self, template: &naming::NameTemplate, context: &naming::NamingContext, current: &str, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+		let mut generations = find_generations(template, context, current)?;
+
+		// The archive is not written in dry-run. Count it as the newest one anyway.
+		if !generations.iter().any(|e| e.path == current) {
+			generations.insert(
+				0,
+				Generation {
+					path: current.to_string(),
+					time: chrono::Local::now(),
+				},
+			);
+		}
+
+		let keep = self.select(&generations, current);
+
+		for (index, generation) in generations.iter().enumerate() {
+			if keep[index] {
+				continue;
+			}
+			// The checksum file and the signature go with the archive.
+			let mut paths = vec![generation.path.to_string()];
+			for extension in SIDECAR_EXTENSIONS {
+				let path = format!("{}{}", &generation.path, extension);
+				if std::path::Path::new(&path).is_file() {
+					paths.push(path);
+				}
+			}
+			for path in &paths {
+				if dry_run {
+					println!("[INFO] (dry-run) EXPIRED {}", path);
+					continue;
+				}
+				println!("[INFO] REMOVE {}", path);
+				std::fs::remove_file(path)?;
+			}
+		}
+
+		return Ok(());
+	}
+
+	/// Decide which generations to keep.
+	///
+	/// # Arguments
+	/// * `generations` Archives sorted from newest to oldest.
+	/// * `current` Path to the archive just created.
+	fn select(&self, generations: &[Generation], current: &str) -> Vec<bool> {
+		let mut keep = vec![!self.has_keep_rules(); generations.len()];
+
+		if let Some(n) = self.keep_last {
+			for e in keep.iter_mut().take(n) {
+				*e = true;
+			}
+		}
+		if let Some(n) = self.keep_daily {
+			keep_by_period(generations, &mut keep, n, "%Y-%m-%d");
+		}
+		if let Some(n) = self.keep_weekly {
+			keep_by_period(generations, &mut keep, n, "%G-W%V");
+		}
+		if let Some(n) = self.keep_monthly {
+			keep_by_period(generations, &mut keep, n, "%Y-%m");
+		}
+
+		// A limit before the range of chrono removes nothing.
+		let limit = self
+			.max_age
+			.and_then(|max_age| chrono::Duration::from_std(max_age).ok())
+			.and_then(|max_age| chrono::Local::now().checked_sub_signed(max_age));
+		if let Some(limit) = limit {
+			for (index, generation) in generations.iter().enumerate() {
+				if generation.time < limit {
+					keep[index] = false;
+				}
+			}
+		}
+
+		// The archive just created always survives.
+		for (index, generation) in generations.iter().enumerate() {
+			if generation.path == current {
+				keep[index] = true;
+			}
+		}
+
+		return keep;
+	}
+}
+
+/// Keep the newest archive of each period, up to `n` periods.
+///
+/// # Arguments
+/// * `format` Format of the period key. (e.g. `%Y-%m` for monthly)
+fn keep_by_period(generations: &[Generation], keep: &mut [bool], n: usize, format: &str) {
+	let mut last_period = String::new();
+	let mut count = 0;
+	for (index, generation) in generations.iter().enumerate() {
+		if n <= count {
+			break;
+		}
+		let period = generation.time.format(format).to_string();
+		if period == last_period {
+			continue;
+		}
+		last_period = period;
+		keep[index] = true;
+		count += 1;
+	}
+}
+
+/// Find archives in the same directory matching the template, sorted from newest to oldest.
+fn find_generations(template: &naming::NameTemplate, context: &naming::NamingContext, current: &str) -> Result<Vec<Generation>, Box<dyn std::error::Error>> {
+	let pattern = template.to_regex(context)?;
+
+	// Directory part of the path as written. (with trailing separator)
+	let prefix = match current.rfind(['/', '\\']) {
+		Some(pos) => &current[..pos + 1],
+		None => "",
+	};
+	let dir = if prefix == "" { "." } else { prefix };
+
+	let mut generations: Vec<Generation> = Vec::new();
+	for e in std::fs::read_dir(dir)? {
+		let entry = e?;
+		let meta = entry.metadata()?;
+		if !meta.is_file() {
+			continue;
+		}
+		let name = entry.file_name();
+		let name = match name.to_str() {
+			Some(name) => name,
+			None => continue,
+		};
+		let path = format!("{}{}", prefix, name);
+		if !pattern.is_match(&path) {
+			continue;
+		}
+		let time = chrono::DateTime::<chrono::Local>::from(meta.modified()?);
+		generations.push(Generation { path, time });
+	}
+
+	generations.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| b.path.cmp(&a.path)));
+
+	return Ok(generations);
+}
@@ -0,0 +1,314 @@
+//!
+//! Detached Ed25519 signatures compatible with minisign.
+//!
+//! * Public key: `"Ed" || key id (8) || public key (32)`
+//! * Secret key: `"Ed" || kdf "\0\0" || "B2" || salt (32) || opslimit (8) || memlimit (8) || key id (8) || secret key (64) || checksum (32)`
+//! * Signature: `"ED" || key id (8) || Ed25519(BLAKE2b-512(file)) (64)`, followed by the trusted comment and its global signature.
+//!
+//! Secret keys are stored without encryption. (same as `minisign -G -W`)
+//!
+
+use crate::util;
+
+/// Signature algorithm. (legacy, the whole content is signed)
+const ALGORITHM_LEGACY: &[u8; 2] = b"Ed";
+
+/// Signature algorithm. (BLAKE2b-512 of the content is signed)
+const ALGORITHM_HASHED: &[u8; 2] = b"ED";
+
+/// Checksum algorithm of the secret key.
+const CHECKSUM_ALGORITHM: &[u8; 2] = b"B2";
+
+/// KDF algorithm of the unencrypted secret key.
+const KDF_NONE: &[u8; 2] = b"\0\0";
+
+/// Length of the decoded secret key.
+const SECRET_KEY_LENGTH: usize = 2 + 2 + 2 + 32 + 8 + 8 + 8 + 64 + 32;
+
+/// Length of the decoded public key.
+const PUBLIC_KEY_LENGTH: usize = 2 + 8 + 32;
+
+/// Length of the decoded signature.
+const SIGNATURE_LENGTH: usize = 2 + 8 + 64;
+
+/// Prefix of the comment lines.
+const UNTRUSTED_COMMENT: &str = "untrusted comment: ";
+
+/// Prefix of the trusted comment line.
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+///
+/// Secret key.
+///
+struct SecretKey {
+	/// Key id.
+	id: [u8; 8],
+
+	/// Ed25519 signing key.
+	key: ed25519_dalek::SigningKey,
+}
+
+///
+/// Public key.
+///
+struct PublicKey {
+	/// Key id.
+	id: [u8; 8],
+
+	/// Ed25519 verifying key.
+	key: ed25519_dalek::VerifyingKey,
+}
+
+/// Generate a new key pair.
+///
+/// # Arguments
+/// * `secret_key_path` Path to the secret key. Existing file is not overwritten.
+/// * `public_key_path` Path to the public key. Existing file is not overwritten.
+pub fn generate_key(secret_key_path: &str, public_key_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+	for path in [secret_key_path, public_key_path] {
+		if std::path::Path::new(path).exists() {
+			return Err(format!("Key file already exists. [{}]", path).into());
+		}
+	}
+
+	let mut id = [0u8; 8];
+	getrandom::getrandom(&mut id)?;
+	let mut seed = [0u8; 32];
+	getrandom::getrandom(&mut seed)?;
+	let key = ed25519_dalek::SigningKey::from_bytes(&seed);
+
+	// Secret key
+	let mut secret: Vec<u8> = Vec::with_capacity(SECRET_KEY_LENGTH);
+	secret.extend_from_slice(ALGORITHM_LEGACY);
+	secret.extend_from_slice(KDF_NONE);
+	secret.extend_from_slice(CHECKSUM_ALGORITHM);
+	secret.extend_from_slice(&[0u8; 32 + 8 + 8]);
+	secret.extend_from_slice(&id);
+	secret.extend_from_slice(&key.to_keypair_bytes());
+	secret.extend_from_slice(&secret_key_checksum(&id, &key.to_keypair_bytes()));
+	let content = format!("{}rzip secret key (unencrypted)\n{}\n", UNTRUSTED_COMMENT, encode(&secret));
+	write_private_file(secret_key_path, &content)?;
+
+	// Public key
+	let mut public: Vec<u8> = Vec::with_capacity(PUBLIC_KEY_LENGTH);
+	public.extend_from_slice(ALGORITHM_LEGACY);
+	public.extend_from_slice(&id);
+	public.extend_from_slice(key.verifying_key().as_bytes());
+	let content = format!("{}minisign public key {}\n{}\n", UNTRUSTED_COMMENT, key_id_to_string(&id), encode(&public));
+	std::fs::write(public_key_path, content)?;
+
+	println!("[INFO] secret key: {}", secret_key_path);
+	println!("[INFO] public key: {} (key id {})", public_key_path, key_id_to_string(&id));
+	return Ok(());
+}
+
+/// Sign a file. The signature is written to `{path}.minisig`.
+///
+/// # Arguments
+/// * `path` Path to the file.
+/// * `secret_key_path` Path to the secret key.
+///
+/// # Returns
+/// Path to the signature.
+pub fn sign(path: &str, secret_key_path: &str) -> Result<String, Box<dyn std::error::Error>> {
+	use crate::util::PathHelper;
+	use ed25519_dalek::Signer;
+
+	let secret_key = read_secret_key(secret_key_path)?;
+
+	let signature = secret_key.key.sign(&hash_file(path)?);
+	let mut encoded: Vec<u8> = Vec::with_capacity(SIGNATURE_LENGTH);
+	encoded.extend_from_slice(ALGORITHM_HASHED);
+	encoded.extend_from_slice(&secret_key.id);
+	encoded.extend_from_slice(&signature.to_bytes());
+
+	let timestamp = chrono::Local::now().timestamp();
+	let name = std::path::Path::new(path).name_as_str();
+	let trusted_comment = format!("timestamp:{}\tfile:{}\thashed", timestamp, name);
+
+	let mut global: Vec<u8> = signature.to_bytes().to_vec();
+	global.extend_from_slice(trusted_comment.as_bytes());
+	let global_signature = secret_key.key.sign(&global);
+
+	let signature_path = format!("{}.minisig", path);
+	let content = format!(
+		"{}signature from rzip secret key\n{}\n{}{}\n{}\n",
+		UNTRUSTED_COMMENT,
+		encode(&encoded),
+		TRUSTED_COMMENT,
+		trusted_comment,
+		encode(&global_signature.to_bytes())
+	);
+	std::fs::write(&signature_path, content)?;
+	return Ok(signature_path);
+}
+
+/// Verify the signature of a file.
+///
+/// # Arguments
+/// * `path` Path to the file.
+/// * `public_key_path` Path to the public key.
+/// * `signature_path` Path to the signature. (default: `{path}.minisig`)
+///
+/// # Returns
+/// Trusted comment.
+pub fn verify(path: &str, public_key_path: &str, signature_path: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+	let public_key = read_public_key(public_key_path)?;
+
+	let signature_path = match signature_path {
+		None => format!("{}.minisig", path),
+		Some(e) => e.to_string(),
+	};
+	let content = util::read_text_file_all(&signature_path)?;
+	let lines: Vec<&str> = content.lines().collect();
+	if lines.len() < 4 || !lines[0].starts_with(UNTRUSTED_COMMENT) || !lines[2].starts_with(TRUSTED_COMMENT) {
+		return Err(format!("Invalid signature file. [{}]", signature_path).into());
+	}
+
+	let encoded = decode(lines[1], SIGNATURE_LENGTH, &signature_path)?;
+	let algorithm = &encoded[0..2];
+	if encoded[2..10] != public_key.id {
+		return Err(format!("Signature was made by another key. (key id {})", key_id_to_string(&encoded[2..10])).into());
+	}
+	let signature = ed25519_dalek::Signature::from_slice(&encoded[10..])?;
+
+	// Signature of the content.
+	let message = if algorithm == ALGORITHM_HASHED {
+		hash_file(path)?
+	} else if algorithm == ALGORITHM_LEGACY {
+		std::fs::read(path)?
+	} else {
+		return Err(format!("Unknown signature algorithm. [{}]", signature_path).into());
+	};
+	if public_key.key.verify_strict(&message, &signature).is_err() {
+		return Err(format!("Signature verification failed. [{}]", path).into());
+	}
+
+	// Signature of the trusted comment.
+	let trusted_comment = lines[2].strip_prefix(TRUSTED_COMMENT).unwrap();
+	let global_signature = ed25519_dalek::Signature::from_slice(&decode(lines[3], 64, &signature_path)?)?;
+	let mut global: Vec<u8> = signature.to_bytes().to_vec();
+	global.extend_from_slice(trusted_comment.as_bytes());
+	if public_key.key.verify_strict(&global, &global_signature).is_err() {
+		return Err(format!("Trusted comment verification failed. [{}]", signature_path).into());
+	}
+
+	return Ok(trusted_comment.to_string());
+}
+
+/// Read a secret key.
+fn read_secret_key(path: &str) -> Result<SecretKey, Box<dyn std::error::Error>> {
+	let content = util::read_text_file_all(path)?;
+	let line = content.lines().find(|e| !e.starts_with(UNTRUSTED_COMMENT)).unwrap_or("");
+	let decoded = decode(line, SECRET_KEY_LENGTH, path)?;
+
+	if &decoded[0..2] != ALGORITHM_LEGACY || &decoded[4..6] != CHECKSUM_ALGORITHM {
+		return Err(format!("Unknown secret key algorithm. [{}]", path).into());
+	}
+	if &decoded[2..4] != KDF_NONE {
+		return Err(format!(
+			"Encrypted secret keys are not supported. [{}] (create one with `rzip keygen` or `minisign -G -W`)",
+			path
+		)
+		.into());
+	}
+
+	let mut id = [0u8; 8];
+	id.copy_from_slice(&decoded[54..62]);
+	let mut keypair = [0u8; 64];
+	keypair.copy_from_slice(&decoded[62..126]);
+	if secret_key_checksum(&id, &keypair)[..] != decoded[126..] {
+		return Err(format!("Broken secret key. (checksum mismatch) [{}]", path).into());
+	}
+
+	let key = ed25519_dalek::SigningKey::from_keypair_bytes(&keypair)?;
+	return Ok(SecretKey { id, key });
+}
+
+/// Read a public key.
+fn read_public_key(path: &str) -> Result<PublicKey, Box<dyn std::error::Error>> {
+	let content = util::read_text_file_all(path)?;
+	let line = content.lines().find(|e| !e.starts_with(UNTRUSTED_COMMENT)).unwrap_or("");
+	let decoded = decode(line, PUBLIC_KEY_LENGTH, path)?;
+
+	if &decoded[0..2] != ALGORITHM_LEGACY {
+		return Err(format!("Unknown public key algorithm. [{}]", path).into());
+	}
+
+	let mut id = [0u8; 8];
+	id.copy_from_slice(&decoded[2..10]);
+	let mut key = [0u8; 32];
+	key.copy_from_slice(&decoded[10..42]);
+	let key = ed25519_dalek::VerifyingKey::from_bytes(&key)?;
+	return Ok(PublicKey { id, key });
+}
+
+/// Checksum of the secret key. BLAKE2b-256 of `"Ed" || key id || secret key`.
+fn secret_key_checksum(id: &[u8], keypair: &[u8]) -> Vec<u8> {
+	use blake2::Digest;
+
+	let mut hasher = blake2::Blake2b::<blake2::digest::consts::U32>::new();
+	hasher.update(ALGORITHM_LEGACY);
+	hasher.update(id);
+	hasher.update(keypair);
+	return hasher.finalize().to_vec();
+}
+
+/// BLAKE2b-512 of a file.
+fn hash_file(path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+	use blake2::Digest;
+	use std::io::Read;
+
+	let mut hasher = blake2::Blake2b512::new();
+	let mut stream = std::fs::File::open(path)?;
+	loop {
+		let mut buffer = [0; 4000];
+		let bytes_read = stream.read(&mut buffer)?;
+		if bytes_read == 0 {
+			break;
+		}
+		hasher.update(&buffer[..bytes_read]);
+	}
+	return Ok(hasher.finalize().to_vec());
+}
+
+/// Key id as printed by minisign. (little endian 64 bit integer in hex)
+fn key_id_to_string(id: &[u8]) -> String {
+	let mut bytes = [0u8; 8];
+	bytes.copy_from_slice(id);
+	return format!("{:016X}", u64::from_le_bytes(bytes));
+}
+
+/// Encode as base64.
+fn encode(bytes: &[u8]) -> String {
+	use base64::Engine;
+
+	return base64::engine::general_purpose::STANDARD.encode(bytes);
+}
+
+/// Decode base64 of the expected length.
+fn decode(line: &str, length: usize, path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+	use base64::Engine;
+
+	let decoded = base64::engine::general_purpose::STANDARD.decode(line.trim());
+	if decoded.is_err() || decoded.as_ref().unwrap().len() != length {
+		return Err(format!("Invalid key or signature file. [{}]", path).into());
+	}
+	return Ok(decoded.unwrap());
+}
+
+/// Write a file readable only by the owner.
+fn write_private_file(path: &str, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+	use std::io::Write;
+
+	let mut options = std::fs::OpenOptions::new();
+	options.write(true).create_new(true);
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::OpenOptionsExt;
+		options.mode(0o600);
+	}
+	let mut file = options.open(path)?;
+	file.write_all(content.as_bytes())?;
+	return Ok(());
+}
@@ -2,6 +2,8 @@
 //! Utilities.
 //!
 
+use crate::configuration;
+
 /// Remove directory or file specified by `path`.
 ///
 /// # Arguments
@@ -21,23 +23,65 @@ pub fn unlink(path: &str) -> Result<(), Box<dyn std::error::Error>> {
 	return Ok(());
 }
 
+/// Get the value of environment variable. Returns empty string if not defined.
+pub fn get_env(name: &str) -> String {
+	let value = std::env::var(name);
+	if value.is_err() {
+		return "".to_string();
+	}
+	return value.unwrap();
+}
+
+/// Get `SOURCE_DATE_EPOCH`. (seconds since the UNIX epoch, see <https://reproducible-builds.org/specs/source-date-epoch/>)
+pub fn source_date_epoch() -> Result<Option<std::time::SystemTime>, Box<dyn std::error::Error>> {
+	let value = get_env("SOURCE_DATE_EPOCH");
+	if value == "" {
+		return Ok(None);
+	}
+	let seconds = value.trim().parse::<u64>();
+	if seconds.is_err() {
+		return Err(format!("Invalid SOURCE_DATE_EPOCH [{}].", value).into());
+	}
+	return Ok(Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds.unwrap())));
+}
+
 /// Get canonical path of `path`.
 pub fn canonicalize_path(path: &str) -> Result<String, Box<dyn std::error::Error>> {
 	let path = std::path::Path::new(path);
 	return path.canonical_path_as_string();
 }
 
+/// Canonicalize the path. Unlike [canonicalize_path], the path may not be valid UTF-8.
+///
+/// # Arguments
+/// * `path` Path to a file or directory.
+pub fn canonicalize(path: &std::path::Path) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+	let path = path.canonicalize()?;
+	return match path.to_str() {
+		None => Ok(path),
+		Some(s) => Ok(std::path::PathBuf::from(fix_unc_path(s))),
+	};
+}
+
 /// Build a path from `path` and `name`.
 ///
 /// # Arguments
-/// * `parent` Parent path.
+/// * `parent` Parent path. (already normalized)
 /// * `name` Name of the file or directory.
+/// * `normalization` Unicode normalization of `name`.
 ///
 /// # Returns
 /// * Path to the file or directory.
-pub fn build_archive_internal_path(parent: &str, name: &str) -> String {
+pub fn build_archive_internal_path(parent: &str, name: &str, normalization: configuration::NameNormalization) -> String {
+	use unicode_normalization::UnicodeNormalization;
+
+	let name = match normalization {
+		configuration::NameNormalization::None => name.to_string(),
+		configuration::NameNormalization::Nfc => name.nfc().collect::<String>(),
+		configuration::NameNormalization::Nfd => name.nfd().collect::<String>(),
+	};
 	if parent == "" {
-		return name.to_string();
+		return name;
 	}
 	return format!("{}/{}", parent, name);
 }
@@ -62,6 +106,112 @@ pub fn timestamp1() -> String {
 	return format!("{}", date.format("%Y%m%d-%H%M%S"));
 }
 
+/// Parse duration like `30d`, `12h`, `2w`. (units: `s`, `m`, `h`, `d`, `w`)
+pub fn parse_duration(value: &str) -> Result<std::time::Duration, Box<dyn std::error::Error>> {
+	let value = value.trim();
+	let pos = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+	let (number, unit) = value.split_at(pos);
+	let number = number.parse::<u64>();
+	if number.is_err() {
+		return Err(format!("Invalid duration [{}]. (e.g. 30d, 12h, 2w)", value).into());
+	}
+	let seconds = match unit {
+		"s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		"d" => 60 * 60 * 24,
+		"w" => 60 * 60 * 24 * 7,
+		_ => return Err(format!("Invalid duration [{}]. (e.g. 30d, 12h, 2w)", value).into()),
+	};
+	return match number.unwrap().checked_mul(seconds) {
+		None => Err(format!("Invalid duration [{}]. (e.g. 30d, 12h, 2w)", value).into()),
+		Some(seconds) => Ok(std::time::Duration::from_secs(seconds)),
+	};
+}
+
+/// Parse size like `100`, `10K`, `1.5M`, `2G`, `1T`. (binary units, optional `B` / `iB` suffix)
+pub fn parse_size(value: &str) -> Result<u64, Box<dyn std::error::Error>> {
+	let value = value.trim();
+	let text = value.trim_end_matches("iB").trim_end_matches('B');
+	let pos = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+	let (number, unit) = text.split_at(pos);
+	let number = number.parse::<f64>();
+	if number.is_err() {
+		return Err(format!("Invalid size [{}]. (e.g. 100K, 10M, 1G)", value).into());
+	}
+	let scale: u64 = match unit.to_uppercase().as_str() {
+		"" => 1,
+		"K" => 1 << 10,
+		"M" => 1 << 20,
+		"G" => 1 << 30,
+		"T" => 1 << 40,
+		_ => return Err(format!("Invalid size [{}]. (e.g. 100K, 10M, 1G)", value).into()),
+	};
+	return Ok((number.unwrap() * scale as f64) as u64);
+}
+
+/// Parse point in time given as a date (`2024-01-31`, `2024-01-31 12:00:00`), a duration before now (`7d`) or a path to a file (its last modified time).
+pub fn parse_time(value: &str) -> Result<std::time::SystemTime, Box<dyn std::error::Error>> {
+	use chrono::TimeZone;
+
+	// Date
+	let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|e| e.and_hms_opt(0, 0, 0).unwrap());
+	let date = date.or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S"));
+	let date = date.or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"));
+	if let Ok(date) = date {
+		if let Some(date) = chrono::Local.from_local_datetime(&date).earliest() {
+			return Ok(std::time::SystemTime::from(date));
+		}
+	}
+
+	// Duration before now
+	if let Ok(duration) = parse_duration(value) {
+		// Also kept in the range of chrono, which formats the time later.
+		let time = chrono::Duration::from_std(duration)
+			.ok()
+			.and_then(|duration| chrono::Local::now().checked_sub_signed(duration));
+		return match time {
+			None => Err(format!("Invalid time [{}]. (too far in the past)", value).into()),
+			Some(time) => Ok(std::time::SystemTime::from(time)),
+		};
+	}
+
+	// File
+	let path = std::path::Path::new(value);
+	if path.exists() {
+		return Ok(path.metadata()?.modified()?);
+	}
+
+	return Err(format!("Invalid time [{}]. (date like 2024-01-31, duration like 7d, or path to a file)", value).into());
+}
+
+/// Format bytes as lowercase hex string.
+pub fn to_hex(bytes: &[u8]) -> String {
+	let mut result = String::with_capacity(bytes.len() * 2);
+	for byte in bytes {
+		result.push_str(&format!("{:02x}", byte));
+	}
+	return result;
+}
+
+/// Calculate SHA-256 of a file.
+pub fn hash_file(path: &std::path::Path) -> Result<String, Box<dyn std::error::Error>> {
+	use sha2::Digest;
+	use std::io::Read;
+
+	let mut hasher = sha2::Sha256::new();
+	let mut stream = std::fs::File::open(path)?;
+	loop {
+		let mut buffer = [0; 4000];
+		let bytes_read = stream.read(&mut buffer)?;
+		if bytes_read == 0 {
+			break;
+		}
+		hasher.update(&buffer[..bytes_read]);
+	}
+	return Ok(to_hex(&hasher.finalize()));
+}
+
 /// Retrieve the whole content of file
 ///
 /// ### Returns
@@ -105,28 +255,6 @@ impl std::fmt::Display for Stopwatch {
 	}
 }
 
-///
-/// [std::fs::DirEntry] helper methods
-///
-pub trait DirEntityHelper {
-	/// Get the path as [String]
-	///
-	/// # Returns
-	/// path as [String]
-	fn path_as_string(&self) -> String;
-}
-
-impl DirEntityHelper for std::fs::DirEntry {
-	/// Get the path as [String]
-	///
-	/// # Returns
-	/// path as [String]
-	fn path_as_string(&self) -> String {
-		let tmp = self.path();
-		return tmp.to_str().unwrap().to_string();
-	}
-}
-
 ///
 /// [std::path::Path] helper methods
 ///
@@ -141,6 +269,7 @@ pub trait PathHelper {
 	///
 	/// # Returns
 	/// name as [String]
+	#[allow(unused)]
 	fn name_as_string(&self) -> String;
 
 	/// Get canonical path as [String]
@@ -149,6 +278,7 @@ pub trait PathHelper {
 	/// canonical path as [String]
 	fn canonical_path_as_string(&self) -> Result<String, Box<dyn std::error::Error>>;
 
+	#[allow(unused)]
 	fn join_as_string(&self, child: &str) -> Result<String, Box<dyn std::error::Error>>;
 }
 
@@ -167,7 +297,7 @@ pub fn concat_path(parent: &str, child: &str) -> String {
 		return child.to_string();
 	}
 	let path = std::path::Path::new(parent);
-	return path.join(child).to_str().unwrap().to_string();
+	return path.join(child).to_string_lossy().to_string();
 }
 
 impl PathHelper for std::path::Path {
@@ -176,7 +306,7 @@ impl PathHelper for std::path::Path {
 	/// # Returns
 	/// name as &str
 	fn name_as_str(&self) -> &str {
-		return self.file_name().unwrap().to_str().unwrap();
+		return self.file_name().and_then(|name| name.to_str()).unwrap_or("");
 	}
 
 	/// Get the name as [String]
@@ -184,7 +314,7 @@ impl PathHelper for std::path::Path {
 	/// # Returns
 	/// name as [String]
 	fn name_as_string(&self) -> String {
-		return self.file_name().unwrap().to_str().unwrap().to_string();
+		return self.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
 	}
 
 	/// Get canonical path as [String]
@@ -193,7 +323,10 @@ impl PathHelper for std::path::Path {
 	/// canonical path as [String]
 	fn canonical_path_as_string(&self) -> Result<String, Box<dyn std::error::Error>> {
 		let path = self.canonicalize()?;
-		let s = path.to_str().unwrap().to_string();
+		let s = match path.to_str() {
+			None => return Err(format!("Path is not valid UTF-8 [{}].", path.display()).into()),
+			Some(s) => s.to_string(),
+		};
 		return Ok(fix_unc_path(&s));
 	}
 
@@ -203,7 +336,10 @@ impl PathHelper for std::path::Path {
 	/// joined path as [String]
 	fn join_as_string(&self, child: &str) -> Result<String, Box<dyn std::error::Error>> {
 		let result = self.join(child);
-		let s = result.to_str().unwrap().to_string();
+		let s = match result.to_str() {
+			None => return Err(format!("Path is not valid UTF-8 [{}].", result.display()).into()),
+			Some(s) => s.to_string(),
+		};
 		return Ok(s);
 	}
 }
@@ -252,14 +388,18 @@ impl DurationFormatter for std::time::Duration {
 /// [chrono::DateTime] helper methods
 ///
 trait ChronoDateTimeHelper {
-	fn as_ziptime(&self) -> zip::DateTime;
+	/// Convert to DOS date time. `None` if out of the range. (1980-01-01 to 2107-12-31)
+	fn as_ziptime(&self) -> Option<zip::DateTime>;
 }
 
-impl ChronoDateTimeHelper for chrono::DateTime<chrono::Local> {
-	fn as_ziptime(&self) -> zip::DateTime {
+impl<Tz: chrono::TimeZone> ChronoDateTimeHelper for chrono::DateTime<Tz> {
+	fn as_ziptime(&self) -> Option<zip::DateTime> {
 		use chrono::{Datelike, Timelike};
 
-		let time = *self;
+		let time = self;
+		if time.year() < 1980 || 2107 < time.year() {
+			return None;
+		}
 		let year = time.year() as u16;
 		let month = time.month() as u8;
 		let day = time.day() as u8;
@@ -267,7 +407,7 @@ impl ChronoDateTimeHelper for chrono::DateTime<chrono::Local> {
 		let min = time.minute() as u8;
 		let sec = time.second() as u8;
 
-		return zip::DateTime::from_date_and_time(year, month, day, hour, min, sec).unwrap();
+		return zip::DateTime::from_date_and_time(year, month, day, hour, min, sec).ok();
 	}
 }
 
@@ -275,14 +415,22 @@ impl ChronoDateTimeHelper for chrono::DateTime<chrono::Local> {
 /// [std::time::SystemTime] helper methods
 ///
 pub trait SystemTimeHelper {
-	fn as_ziptime(&self) -> zip::DateTime;
+	/// Convert to DOS date time in local time. `None` if out of the range.
+	fn as_ziptime(&self) -> Option<zip::DateTime>;
+
+	/// Convert to DOS date time in UTC, independent of the time zone of the host. `None` if out of the range.
+	fn as_utc_ziptime(&self) -> Option<zip::DateTime>;
 }
 
 impl SystemTimeHelper for std::time::SystemTime {
-	fn as_ziptime(&self) -> zip::DateTime {
+	fn as_ziptime(&self) -> Option<zip::DateTime> {
 		let val1 = chrono::DateTime::<chrono::Local>::from(*self);
 		let val2 = val1.as_ziptime();
 		// let val2 = convert_datetime2(val1);
 		return val2;
 	}
+
+	fn as_utc_ziptime(&self) -> Option<zip::DateTime> {
+		return chrono::DateTime::<chrono::Utc>::from(*self).as_ziptime();
+	}
 }